@@ -0,0 +1,231 @@
+// Command line arguments, parsed by hand to keep the crate free of dependencies
+use ferromagnetic::CoordinateSystem;
+
+use crate::output::Format;
+
+pub const USAGE: &str = "\
+Usage:
+  ferromagnetic [OPTIONS] <LAT> <LON> <ALT> <DATE>
+  ferromagnetic [OPTIONS] --file <PATH>
+  ferromagnetic [OPTIONS] --geomag70 <PATH>
+  ferromagnetic table [OPTIONS] <LAT> <LON> <ALT> <FROM> <TO>
+
+Calculates declination (D), inclination (I), horizontal (H), north (X), east (Y),
+down (Z) and total (F) intensity with their annual change. Latitude and longitude
+are in degrees, altitude in km above the WGS84 ellipsoid and date in decimal years
+or as an ISO 8601 date and time (UTC unless an offset is given), e.g. 2024-07-02 or
+2024-07-02T12:30Z. `table` writes a reference table at one location for the dates
+FROM to TO in the format of the IGRF test values.
+
+Input:
+  --file <PATH>         Points as LAT LON ALT DATE per line, separated by commas or
+                        whitespace, `-` reads standard input
+  --geomag70 <PATH>     Points in the batch format of geomag70:
+                        DATE D|C K|M|F<ALT> LAT LON per line
+  --geocentric          LAT is geocentric and ALT is the radius (km) from the
+                        centre of the Earth for point and --file input
+
+Options:
+  -m, --model <NAME>    Model to use, default IGRF-14, see --list-models
+  --coeffs <PATH>       Read the model from a .COF or .shc coefficient file
+  -f, --format <FMT>    table, csv, json or geomag70, default table, or geomag70
+                        for --geomag70 input
+  --step <YEARS>        Years between the lines of a table, default 1
+  --label <TEXT>        Text after the location on the first line of a table
+  --list-models         Lists the builtin models
+  -h, --help            Prints this help
+";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Help,
+    ListModels,
+    Run(Box<Options>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModelChoice {
+    Name(String),
+    File(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    Point {
+        lat: String,
+        lon: String,
+        alt: String,
+        date: String,
+    },
+    File(String),
+    Geomag70(String),
+    Table(TableInput),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableInput {
+    pub lat: String,
+    pub lon: String,
+    pub alt: String,
+    pub from: String,
+    pub to: String,
+    pub step: String,
+    pub label: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub model: ModelChoice,
+    pub format: Format,
+    pub coords: CoordinateSystem,
+    pub input: Input,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    let table = args.next_if(|arg| arg == "table").is_some();
+    let mut step = None;
+    let mut label = None;
+    let mut model = ModelChoice::Name("IGRF-14".to_string());
+    let mut format = None;
+    let mut coords = CoordinateSystem::Geodetic;
+    let mut input = None;
+    let mut positional = vec![];
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--list-models" => return Ok(Command::ListModels),
+            "-m" | "--model" => model = ModelChoice::Name(value(&arg)?),
+            "--coeffs" => model = ModelChoice::File(value(&arg)?),
+            "-f" | "--format" => format = Some(Format::parse(&value(&arg)?)?),
+            "--geocentric" => coords = CoordinateSystem::Geocentric,
+            "--file" => input = Some(Input::File(value(&arg)?)),
+            "--geomag70" => input = Some(Input::Geomag70(value(&arg)?)),
+            "--step" => step = Some(value(&arg)?),
+            "--label" => label = Some(value(&arg)?),
+            // negative numbers are values, not options
+            _ if arg.starts_with('-') && arg.parse::<f64>().is_err() => {
+                return Err(format!("unknown option {}", arg))
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if table {
+        let [lat, lon, alt, from, to] = positional.as_slice() else {
+            return Err("a table needs LAT LON ALT FROM TO".to_string());
+        };
+        if input.is_some() || format.is_some() {
+            return Err("a table is written for one point in its own format".to_string());
+        }
+        return Ok(Command::Run(Box::new(Options {
+            model,
+            format: Format::Table,
+            coords,
+            input: Input::Table(TableInput {
+                lat: lat.clone(),
+                lon: lon.clone(),
+                alt: alt.clone(),
+                from: from.clone(),
+                to: to.clone(),
+                step: step.unwrap_or_else(|| "1".to_string()),
+                label: label.unwrap_or_default(),
+            }),
+        })));
+    }
+    if step.is_some() || label.is_some() {
+        return Err("--step and --label only apply to tables".to_string());
+    }
+    let input = match (input, positional.as_slice()) {
+        (Some(input), []) => input,
+        (None, [lat, lon, alt, date]) => Input::Point {
+            lat: lat.clone(),
+            lon: lon.clone(),
+            alt: alt.clone(),
+            date: date.clone(),
+        },
+        (Some(_), _) => return Err("a point can not be given together with a file".to_string()),
+        (None, []) => return Err("no point or file given".to_string()),
+        (None, _) => return Err("a point needs LAT LON ALT DATE".to_string()),
+    };
+    let format = format.unwrap_or(match input {
+        Input::Geomag70(_) => Format::Geomag70,
+        _ => Format::Table,
+    });
+    Ok(Command::Run(Box::new(Options {
+        model,
+        format,
+        coords,
+        input,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &str) -> Result<Command, String> {
+        parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn point_and_options() {
+        assert_eq!(
+            parse_str("-m wmm2020 -f json -33.9 -18.4 0.5 2021.25"),
+            Ok(Command::Run(Box::new(Options {
+                model: ModelChoice::Name("wmm2020".to_string()),
+                format: Format::Json,
+                coords: CoordinateSystem::Geodetic,
+                input: Input::Point {
+                    lat: "-33.9".to_string(),
+                    lon: "-18.4".to_string(),
+                    alt: "0.5".to_string(),
+                    date: "2021.25".to_string(),
+                },
+            })))
+        );
+        assert_eq!(parse_str("1 2 3 --help"), Ok(Command::Help));
+    }
+
+    #[test]
+    fn table() {
+        let Ok(Command::Run(options)) = parse_str("table -m IGRF-13 --step 0.5 60 5 0 2000 2010")
+        else {
+            panic!("not parsed");
+        };
+        assert_eq!(
+            options.input,
+            Input::Table(TableInput {
+                lat: "60".to_string(),
+                lon: "5".to_string(),
+                alt: "0".to_string(),
+                from: "2000".to_string(),
+                to: "2010".to_string(),
+                step: "0.5".to_string(),
+                label: String::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn geomag70_defaults_to_its_format() {
+        let Ok(Command::Run(options)) = parse_str("--geomag70 - --coeffs IGRF13.COF") else {
+            panic!("not parsed");
+        };
+        assert_eq!(options.format, Format::Geomag70);
+        assert_eq!(options.model, ModelChoice::File("IGRF13.COF".to_string()));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse_str("").is_err());
+        assert!(parse_str("1 2 3").is_err());
+        assert!(parse_str("--file a 1 2 3 4").is_err());
+        assert!(parse_str("--frobnicate 1 2 3 4").is_err());
+        assert!(parse_str("-f xml 1 2 3 4").is_err());
+        assert!(parse_str("1 2 3 4 --model").is_err());
+        assert!(parse_str("--step 2 1 2 3 4").is_err());
+        assert!(parse_str("table 1 2 3 4").is_err());
+        assert!(parse_str("table -f csv 1 2 3 4 5").is_err());
+    }
+}
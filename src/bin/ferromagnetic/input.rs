@@ -0,0 +1,214 @@
+// Points from the command line, column files and geomag70 batch files
+use std::io::BufRead;
+
+use ferromagnetic::{CoordinateSystem, ModelDate};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point {
+    pub lat: f64,
+    pub lon: f64,
+    // altitude (km), or radius (km) for geocentric input
+    pub alt: f64,
+    pub date: f64,
+    pub coords: CoordinateSystem,
+    // the date, coordinate system, altitude, latitude and longitude as written in a
+    // geomag70 batch file
+    pub echo: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    Columns,
+    Geomag70,
+}
+
+fn number(name: &str, s: &str) -> Result<f64, String> {
+    s.trim()
+        .parse::<f64>()
+        .map_err(|_| format!("invalid {} `{}`", name, s))
+}
+
+// Decimal year or ISO 8601 date and time
+pub fn date(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    match s.parse::<f64>() {
+        Ok(year) => Ok(year),
+        Err(_) => s
+            .parse::<ModelDate>()
+            .map(ModelDate::decimal_year)
+            .map_err(|err| err.to_string()),
+    }
+}
+
+pub fn point(
+    coords: CoordinateSystem,
+    lat: &str,
+    lon: &str,
+    alt: &str,
+    date: &str,
+) -> Result<Point, String> {
+    Ok(Point {
+        lat: number("latitude", lat)?,
+        lon: number("longitude", lon)?,
+        alt: number("altitude", alt)?,
+        date: self::date(date)?,
+        coords,
+        echo: None,
+    })
+}
+
+// LAT LON ALT DATE separated by commas, semicolons or whitespace
+fn columns(line: &str, coords: CoordinateSystem) -> Result<Point, String> {
+    let fields = line
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|f| !f.is_empty())
+        .collect::<Vec<_>>();
+    match fields.as_slice() {
+        [lat, lon, alt, date] => point(coords, lat, lon, alt, date),
+        _ => Err(format!("expected 4 columns, found {}", fields.len())),
+    }
+}
+
+// Decimal year or yyyy,mm,dd
+fn geomag70_date(s: &str) -> Result<f64, String> {
+    let parts = s.split(',').collect::<Vec<_>>();
+    let [year, month, day] = parts.as_slice() else {
+        return number("date", s);
+    };
+    let invalid = || format!("invalid date `{}`", s);
+    let year = year.parse::<i32>().map_err(|_| invalid())?;
+    let month = month.parse::<u32>().map_err(|_| invalid())?;
+    let day = day.parse::<u32>().map_err(|_| invalid())?;
+    ModelDate::from_ymd(year, month, day)
+        .map(ModelDate::decimal_year)
+        .map_err(|_| invalid())
+}
+
+// Decimal degrees or degrees,minutes,seconds
+fn geomag70_angle(name: &str, s: &str) -> Result<f64, String> {
+    let parts = s.split(',').collect::<Vec<_>>();
+    if parts.len() == 1 {
+        return number(name, s);
+    }
+    if parts.len() > 3 {
+        return Err(format!("invalid {} `{}`", name, s));
+    }
+    let values = parts
+        .iter()
+        .map(|p| number(name, p))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sign = if s.trim_start().starts_with('-') {
+        -1.
+    } else {
+        1.
+    };
+    let magnitude = values
+        .iter()
+        .zip([1., 60., 3600.])
+        .map(|(v, scale)| v.abs() / scale)
+        .sum::<f64>();
+    Ok(sign * magnitude)
+}
+
+// Altitude in km, m or feet as K, M or F followed by the value
+fn geomag70_altitude(s: &str) -> Result<f64, String> {
+    let mut chars = s.chars();
+    let scale = match chars.next().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => 1.,
+        Some('M') => 0.001,
+        Some('F') => 0.0003048,
+        _ => return Err(format!("altitude `{}` does not start with K, M or F", s)),
+    };
+    Ok(number("altitude", chars.as_str())? * scale)
+}
+
+// DATE D|C ALT LAT LON, anything after is ignored as geomag70 does
+fn geomag70(line: &str) -> Result<Point, String> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    if fields.len() < 5 {
+        return Err(format!("expected 5 columns, found {}", fields.len()));
+    }
+    let coords = match fields[1] {
+        "D" | "d" => CoordinateSystem::Geodetic,
+        "C" | "c" => CoordinateSystem::Geocentric,
+        other => return Err(format!("coordinate system `{}` is not D or C", other)),
+    };
+    Ok(Point {
+        date: geomag70_date(fields[0])?,
+        coords,
+        alt: geomag70_altitude(fields[2])?,
+        lat: geomag70_angle("latitude", fields[3])?,
+        lon: geomag70_angle("longitude", fields[4])?,
+        echo: Some(fields[..5].join(" ")),
+    })
+}
+
+// Points of a file with their 1-based line numbers, skipping blank lines, `#` comments
+// and a header line before the first point of a column file
+pub fn points<R: BufRead>(
+    reader: R,
+    kind: FileKind,
+    coords: CoordinateSystem,
+) -> impl Iterator<Item = (usize, Result<Point, String>)> {
+    let mut first = true;
+    reader.lines().enumerate().filter_map(move |(i, line)| {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => return Some((i + 1, Err(err.to_string()))),
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let point = match kind {
+            FileKind::Columns => columns(line, coords),
+            FileKind::Geomag70 => geomag70(line),
+        };
+        let numeric = line.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c));
+        let header = first && kind == FileKind::Columns && !numeric;
+        first = false;
+        if point.is_err() && header {
+            return None;
+        }
+        Some((i + 1, point))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn column_files() {
+        let file = "lat,lon,alt,date\n# comment\n\n59.9, 10.7, 0, 2020.5\n-33.9 18.4 1.5 2021-07-02T12:00Z\nx\n";
+        let points = points(
+            file.as_bytes(),
+            FileKind::Columns,
+            CoordinateSystem::Geodetic,
+        )
+        .collect::<Vec<_>>();
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0].0, 4);
+        assert_eq!(points[1].1.as_ref().unwrap().alt, 1.5);
+        assert_eq!(points[1].1.as_ref().unwrap().date, 2021.5);
+        assert!(points[2].1.is_err());
+    }
+
+    #[test]
+    fn geomag70_lines() {
+        let point = geomag70("2016,7,2 C M1500 -40,30,0 105.5 extra").unwrap();
+        assert_eq!(point.coords, CoordinateSystem::Geocentric);
+        assert_eq!(point.date, 2016. + 183. / 366.);
+        assert_eq!(point.alt, 1.5);
+        assert_eq!(point.lat, -40.5);
+        assert_eq!(point.lon, 105.5);
+        assert_eq!(
+            point.echo.as_deref(),
+            Some("2016,7,2 C M1500 -40,30,0 105.5")
+        );
+        assert!((geomag70_altitude("F1000").unwrap() - 0.3048).abs() < 1e-12);
+        assert!(geomag70("2017.5 X K0 1 2").is_err());
+        assert!(geomag70("2017,2,29 D K0 1 2").is_err());
+        assert!(geomag70("2017.5 D 10 1 2").is_err());
+    }
+}
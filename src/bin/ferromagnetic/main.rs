@@ -0,0 +1,156 @@
+//! Command line tool calculating the geomagnetic field at a point or for every point of a file.
+mod args;
+mod input;
+mod output;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::process::ExitCode;
+
+use ferromagnetic::cof::CofModel;
+use ferromagnetic::igrf::IGRF;
+use ferromagnetic::{table, CoordinateSystem, GeomagneticModel, ReferenceTable, Registry};
+
+use args::{Command, Input, ModelChoice, Options, TableInput};
+
+// Longest reference table written, guards against a tiny --step
+const MAX_TABLE_LINES: f64 = 1e6;
+use input::FileKind;
+use output::Writer;
+
+fn load_model(choice: &ModelChoice) -> Result<Box<dyn GeomagneticModel>, String> {
+    match choice {
+        ModelChoice::Name(name) => Registry::builtin()
+            .remove(name)
+            .ok_or_else(|| format!("unknown model {}, see --list-models", name)),
+        ModelChoice::File(path) => {
+            let error = |err: &dyn std::fmt::Display| format!("{}: {}", path, err);
+            let contents = std::fs::read_to_string(path).map_err(|err| error(&err))?;
+            if path.to_ascii_lowercase().ends_with(".cof") {
+                let model = CofModel::from_cof_str(&contents).map_err(|err| error(&err))?;
+                Ok(Box::new(model))
+            } else {
+                let model = IGRF::from_shc_str(&contents).map_err(|err| error(&err))?;
+                Ok(Box::new(model))
+            }
+        }
+    }
+}
+
+fn open(path: &str) -> Result<Box<dyn BufRead>, String> {
+    if path == "-" {
+        return Ok(Box::new(BufReader::new(io::stdin())));
+    }
+    let file = File::open(path).map_err(|err| format!("{}: {}", path, err))?;
+    Ok(Box::new(BufReader::new(file)))
+}
+
+// Writes a reference table of the field at one location
+fn write_table(
+    model: &dyn GeomagneticModel,
+    coords: CoordinateSystem,
+    input: &TableInput,
+) -> Result<(), String> {
+    let point = input::point(coords, &input.lat, &input.lon, &input.alt, &input.from)?;
+    let to = input::date(&input.to)?;
+    if !to.is_finite() {
+        return Err(format!("invalid date `{}`", input.to));
+    }
+    let step = input
+        .step
+        .parse::<f64>()
+        .ok()
+        .filter(|step| step.is_finite() && *step > 0.)
+        .ok_or_else(|| format!("invalid step `{}`", input.step))?;
+    if (to - point.date) / step > MAX_TABLE_LINES {
+        return Err(format!("step `{}` gives too many lines", input.step));
+    }
+    let dates = table::yearly(point.date, to, step);
+    let (lat, lon, alt) = (point.lat, point.lon, point.alt);
+    let table = ReferenceTable::generate(model, coords, lat, lon, alt, dates)
+        .map_err(|err| err.to_string())?
+        .with_label(&input.label);
+    print!("{}", table);
+    Ok(())
+}
+
+// Writes the results of every point, reporting bad points on stderr.
+// Returns false if any point failed.
+fn run(options: &Options) -> Result<bool, String> {
+    let model = load_model(&options.model)?;
+    if let Input::Table(table) = &options.input {
+        write_table(model.as_ref(), options.coords, table)?;
+        return Ok(true);
+    }
+    let points: Box<dyn Iterator<Item = (usize, Result<input::Point, String>)>> =
+        match &options.input {
+            Input::Point {
+                lat,
+                lon,
+                alt,
+                date,
+            } => Box::new(std::iter::once((
+                0,
+                input::point(options.coords, lat, lon, alt, date),
+            ))),
+            Input::File(path) => Box::new(input::points(
+                open(path)?,
+                FileKind::Columns,
+                options.coords,
+            )),
+            Input::Geomag70(path) => Box::new(input::points(
+                open(path)?,
+                FileKind::Geomag70,
+                options.coords,
+            )),
+            Input::Table(_) => unreachable!("tables are written by write_table"),
+        };
+    let io_error = |err: io::Error| err.to_string();
+    let mut writer = Writer::new(io::stdout().lock(), options.format, model.name());
+    let mut ok = true;
+    for (line, point) in points {
+        let result = point.and_then(|point| {
+            model
+                .try_calc_in(point.coords, point.lat, point.lon, point.alt, point.date)
+                .map(|field| (point, field))
+                .map_err(|err| err.to_string())
+        });
+        match result {
+            Ok((point, field)) => writer.row(&point, &field).map_err(io_error)?,
+            Err(err) if line == 0 => return Err(err),
+            Err(err) => {
+                eprintln!("ferromagnetic: line {}: {}", line, err);
+                ok = false;
+            }
+        }
+    }
+    writer.finish().map_err(io_error)?;
+    Ok(ok)
+}
+
+fn main() -> ExitCode {
+    match args::parse(std::env::args().skip(1)) {
+        Ok(Command::Help) => {
+            print!("{}", args::USAGE);
+            ExitCode::SUCCESS
+        }
+        Ok(Command::ListModels) => {
+            for model in Registry::builtin().names() {
+                println!("{}", model);
+            }
+            ExitCode::SUCCESS
+        }
+        Ok(Command::Run(options)) => match run(&options) {
+            Ok(true) => ExitCode::SUCCESS,
+            Ok(false) => ExitCode::FAILURE,
+            Err(err) => {
+                eprintln!("ferromagnetic: {}", err);
+                ExitCode::FAILURE
+            }
+        },
+        Err(err) => {
+            eprintln!("ferromagnetic: {}\n\n{}", err, args::USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
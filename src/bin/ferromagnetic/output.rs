@@ -0,0 +1,263 @@
+// Writes results as an aligned table, CSV, JSON or geomag70's batch output
+use std::io::{self, Write};
+
+use ferromagnetic::{CoordinateSystem, FieldResults, MagneticComponents};
+
+use crate::input::Point;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+    Geomag70,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Result<Format, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "table" => Ok(Format::Table),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "geomag70" => Ok(Format::Geomag70),
+            _ => Err(format!("unknown format {}", s)),
+        }
+    }
+}
+
+const NAMES: [&str; 7] = ["D", "I", "H", "X", "Y", "Z", "F"];
+
+fn values(c: &MagneticComponents) -> [f64; 7] {
+    let o = &c.orthogonal_strength;
+    [
+        c.declination,
+        c.inclination,
+        c.horizontal_intensity,
+        o.north,
+        o.east,
+        o.down,
+        c.total_intensity,
+    ]
+}
+
+// JSON has no NaN or infinity
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Whole degrees and minutes rounded as geomag70 prints them
+fn degrees_minutes(value: f64) -> (i32, f64) {
+    let mut deg = value.trunc() as i32;
+    let mut min = (value - deg as f64) * 60.;
+    if value > 0. && min >= 59.5 {
+        min -= 60.;
+        deg += 1;
+    }
+    if value < 0. && min <= -59.5 {
+        min += 60.;
+        deg -= 1;
+    }
+    if deg != 0 {
+        min = min.abs();
+    }
+    (deg, min)
+}
+
+pub struct Writer<W: Write> {
+    out: W,
+    format: Format,
+    model: String,
+    // the header is written with the first row, so nothing is written if the only point fails
+    header: bool,
+    rows: usize,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(out: W, format: Format, model: &str) -> Writer<W> {
+        Writer {
+            out,
+            format,
+            model: model.to_string(),
+            header: false,
+            rows: 0,
+        }
+    }
+
+    fn header(&mut self) -> io::Result<()> {
+        if self.header {
+            return Ok(());
+        }
+        self.header = true;
+        match self.format {
+            Format::Table => {
+                writeln!(self.out, "Model: {}", self.model)?;
+                write!(
+                    self.out,
+                    "{:>8} {:>9} {:>9} {:>10}",
+                    "LAT", "LON", "ALT", "DATE"
+                )?;
+                for name in NAMES {
+                    let width = if name == "D" || name == "I" { 8 } else { 10 };
+                    write!(self.out, " {:>width$} {:>7}", name, format!("d{}", name))?;
+                }
+                writeln!(self.out)
+            }
+            Format::Csv => {
+                write!(self.out, "lat,lon,alt,date")?;
+                for name in NAMES {
+                    write!(self.out, ",{}", name)?;
+                }
+                for name in NAMES {
+                    write!(self.out, ",d{}", name)?;
+                }
+                writeln!(self.out)
+            }
+            Format::Json => write!(
+                self.out,
+                "{{\"model\":{},\"results\":[",
+                json_string(&self.model)
+            ),
+            Format::Geomag70 => writeln!(
+                self.out,
+                "Date Coord-System Altitude Latitude Longitude D_deg D_min I_deg I_min \
+                 H_nT X_nT Y_nT Z_nT F_nT dD_min dI_min dH_nT dX_nT dY_nT dZ_nT dF_nT"
+            ),
+        }
+    }
+
+    pub fn row(&mut self, point: &Point, field: &FieldResults) -> io::Result<()> {
+        self.header()?;
+        let (value, sv) = (values(&field.result), values(&field.sv));
+        match self.format {
+            Format::Table => {
+                write!(
+                    self.out,
+                    "{:>8.3} {:>9.3} {:>9.3} {:>10.4}",
+                    point.lat, point.lon, point.alt, point.date
+                )?;
+                for i in 0..NAMES.len() {
+                    if i < 2 {
+                        write!(self.out, " {:>8.2} {:>7.1}", value[i], sv[i])?;
+                    } else {
+                        write!(self.out, " {:>10.1} {:>7.1}", value[i], sv[i])?;
+                    }
+                }
+                writeln!(self.out)
+            }
+            Format::Csv => {
+                write!(
+                    self.out,
+                    "{},{},{},{}",
+                    point.lat, point.lon, point.alt, point.date
+                )?;
+                for v in value.iter().chain(&sv) {
+                    write!(self.out, ",{}", v)?;
+                }
+                writeln!(self.out)
+            }
+            Format::Json => {
+                if self.rows > 0 {
+                    write!(self.out, ",")?;
+                }
+                let coords = match point.coords {
+                    CoordinateSystem::Geodetic => "geodetic",
+                    CoordinateSystem::Geocentric => "geocentric",
+                };
+                write!(
+                    self.out,
+                    "\n{{\"lat\":{},\"lon\":{},\"alt\":{},\"date\":{},\"coords\":\"{}\"",
+                    json_number(point.lat),
+                    json_number(point.lon),
+                    json_number(point.alt),
+                    json_number(point.date),
+                    coords
+                )?;
+                for (i, name) in NAMES.iter().enumerate() {
+                    write!(self.out, ",\"{}\":{}", name, json_number(value[i]))?;
+                }
+                for (i, name) in NAMES.iter().enumerate() {
+                    write!(self.out, ",\"d{}\":{}", name, json_number(sv[i]))?;
+                }
+                write!(self.out, "}}")
+            }
+            Format::Geomag70 => {
+                let echo = point.echo.clone().unwrap_or_else(|| {
+                    let coords = match point.coords {
+                        CoordinateSystem::Geodetic => "D",
+                        CoordinateSystem::Geocentric => "C",
+                    };
+                    format!(
+                        "{} {} K{} {} {}",
+                        point.date, coords, point.alt, point.lat, point.lon
+                    )
+                });
+                let (d_deg, d_min) = degrees_minutes(value[0]);
+                let (i_deg, i_min) = degrees_minutes(value[1]);
+                write!(
+                    self.out,
+                    "{} {:4}d {:3.0}m {:4}d {:3.0}m",
+                    echo, d_deg, d_min, i_deg, i_min
+                )?;
+                write!(
+                    self.out,
+                    " {:8.1} {:8.1} {:8.1} {:8.1} {:9.1}",
+                    value[2], value[3], value[4], value[5], value[6]
+                )?;
+                writeln!(
+                    self.out,
+                    " {:7.1} {:7.1} {:8.1} {:8.1} {:8.1} {:8.1} {:8.1}",
+                    sv[0], sv[1], sv[2], sv[3], sv[4], sv[5], sv[6]
+                )
+            }
+        }?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.header()?;
+        if self.format == Format::Json {
+            writeln!(self.out, "\n]}}")?;
+        }
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geomag70_degrees_and_minutes() {
+        assert_eq!(degrees_minutes(12.5), (12, 30.));
+        assert_eq!(degrees_minutes(-3.25), (-3, 15.));
+        assert_eq!(degrees_minutes(-0.5), (0, -30.));
+        let (deg, min) = degrees_minutes(4.995);
+        assert_eq!(deg, 5);
+        assert!((min - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn json_escapes() {
+        assert_eq!(json_string("a\"b\\\n"), "\"a\\\"b\\\\\\u000a\"");
+        assert_eq!(json_number(f64::NAN), "null");
+    }
+}
@@ -0,0 +1,153 @@
+//! Fast evaluation along a circle of latitude.
+use crate::error::check_input;
+use crate::fft::{Complex, Fft};
+use crate::igrf::SecularVariation;
+use crate::math::{self, Position};
+use crate::sh::{self, OrderSums};
+use crate::{CoordinateSystem, FieldResults, GeomagError, ModelSnapshot, Provenance};
+
+/// The field along a circle of fixed latitude and altitude at one date.
+///
+/// Everything that depends on latitude and altitude is calculated when the circle is
+/// created, each longitude then costs one pass over the orders of the model.
+/// Created with [`ModelSnapshot::try_circle`](crate::ModelSnapshot::try_circle).
+pub struct MagneticCircle {
+    coords: CoordinateSystem,
+    lat: f64,
+    height: f64,
+    date: f64,
+    pos: Position,
+    // reference radius (km) of the expansion
+    radius: f64,
+    // per order sums from nmax down to 0, for the coefficients and their annual change
+    // or the coefficients a year later
+    sums: Vec<(usize, OrderSums)>,
+    other: Vec<(usize, OrderSums)>,
+    sv_mode: SecularVariation,
+    provenance: Provenance,
+}
+
+fn all_order_sums(nmax: usize, gh: &[f64], point: &sh::Point) -> Vec<(usize, OrderSums)> {
+    sh::orders(nmax)
+        .map(|(m, q_mm)| {
+            let sums = sh::order_sums(nmax, gh, m, q_mm, point.t, point.ratio);
+            (m, sums)
+        })
+        .collect()
+}
+
+impl MagneticCircle {
+    // Circle at `lat`/`height`, checked by the caller
+    pub(crate) fn new(
+        snapshot: &ModelSnapshot,
+        coords: CoordinateSystem,
+        lat: f64,
+        height: f64,
+    ) -> MagneticCircle {
+        let pos = Position::new(lat, 0., height, coords);
+        let nmax = snapshot.nmax;
+        let point = pos.point(snapshot.radius);
+        MagneticCircle {
+            coords,
+            lat,
+            height,
+            date: snapshot.date,
+            sums: all_order_sums(nmax, &snapshot.coeffs, &point),
+            other: all_order_sums(nmax, &snapshot.other, &point),
+            pos,
+            radius: snapshot.radius,
+            sv_mode: snapshot.sv_mode,
+            provenance: snapshot.provenance.at(lat),
+        }
+    }
+
+    /// Latitude (degrees) of the circle
+    pub fn lat(&self) -> f64 {
+        self.lat
+    }
+
+    /// Altitude, or radius for geocentric input, (km) of the circle
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    /// Calculates the field at longitude `lon` (degrees),
+    /// panics if the longitude is invalid, see [`MagneticCircle::try_calc`]
+    pub fn calc(&self, lon: f64) -> FieldResults {
+        self.try_calc(lon).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Calculates the field at longitude `lon` (degrees), the results are the same as
+    /// evaluating the position with [`ModelSnapshot::try_calc_in`](crate::ModelSnapshot::try_calc_in)
+    pub fn try_calc(&self, lon: f64) -> Result<FieldResults, GeomagError> {
+        // latitude and altitude were checked when the circle was created
+        check_input(
+            self.coords,
+            self.lat,
+            lon,
+            self.height,
+            self.date,
+            (f64::MIN, f64::MAX),
+        )?;
+        let lon = math::longitude(lon);
+        let a = self.pos.field(self.field(&self.sums, lon));
+        let b = self.pos.field(self.field(&self.other, lon));
+        Ok(FieldResults::new(self.sv_mode, self.provenance, a, b))
+    }
+
+    // Field at `out.len()` longitudes evenly spaced around the circle from `start_lon`
+    // (degrees), found with one inverse FFT per pair of components instead of summing
+    // the orders at every longitude. The longitudes are exact rather than converted to
+    // radians with geomag70's truncated factor. `scratch` holds
+    // `6 * fft.len() + fft.scratch_len()` values and is reused between circles.
+    pub(crate) fn calc_around(
+        &self,
+        fft: &Fft,
+        start_lon: f64,
+        scratch: &mut [Complex],
+        out: &mut [FieldResults],
+    ) {
+        let n = fft.len();
+        let point = self.pos.point(self.radius);
+        let (t, u) = (point.t, point.u);
+        // x and y of the coefficients, then z of both, then x and y of `other`, followed
+        // by their transforms and the scratch of the FFT itself
+        let (spectra, rest) = scratch.split_at_mut(3 * n);
+        let (values, fft_scratch) = rest.split_at_mut(3 * n);
+        spectra.fill(Complex::default());
+        for (sums, other) in self.sums.iter().zip(&self.other) {
+            let m = sums.0;
+            let [xa, ya, za] = sh::order_fourier(m, &sums.1, t, u);
+            let [xb, yb, zb] = sh::order_fourier(m, &other.1, t, u);
+            let phase = Complex::cis(m as f64 * start_lon.to_radians());
+            for (spectrum, (f, g)) in spectra.chunks_mut(n).zip([(xa, ya), (za, zb), (xb, yb)]) {
+                // f = Re(F e^(i m lon)) with F = (cos - i sin) e^(i m start_lon), half of it
+                // goes to m and the conjugate to -m so the transform of F + i G is f + i g
+                let f = Complex::new(f.0, -f.1) * phase;
+                let g = Complex::new(g.0, -g.1) * phase;
+                let i = Complex::new(0., 1.);
+                let (pos, neg) = (m % n, (n - m % n) % n);
+                spectrum[pos] = spectrum[pos] + (f + i * g) * 0.5;
+                spectrum[neg] = spectrum[neg] + (f.conj() + i * g.conj()) * 0.5;
+            }
+        }
+        for (spectrum, values) in spectra.chunks(n).zip(values.chunks_mut(n)) {
+            fft.inverse(spectrum, values, fft_scratch);
+        }
+        let (xy_a, rest) = values.split_at(n);
+        let (z, xy_b) = rest.split_at(n);
+        for (k, out) in out.iter_mut().enumerate() {
+            let a = self.pos.field((xy_a[k].re, xy_a[k].im, z[k].re));
+            let b = self.pos.field((xy_b[k].re, xy_b[k].im, z[k].im));
+            *out = FieldResults::new(self.sv_mode, self.provenance, a, b);
+        }
+    }
+
+    fn field(&self, sums: &[(usize, OrderSums)], lon: f64) -> (f64, f64, f64) {
+        let point = sh::Point {
+            lon,
+            ..self.pos.point(self.radius)
+        };
+        sh::field_from_sums(sums.iter().copied(), &point)
+    }
+}
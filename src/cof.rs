@@ -0,0 +1,428 @@
+//! Reader for geomag70 style `.COF` coefficient files.
+//!
+//! A file consists of one or more blocks, each starting with a header line
+//!
+//! ```text
+//!    IGRF00  1900.00 10  0  0 1900.00 1905.00   -1.0  600.0           IGRF00   0
+//! ```
+//!
+//! giving model name, epoch, max1 (main field degree), max2 (secular variation degree),
+//! max3 (acceleration degree, unused), yrmin, yrmax, altmin and altmax (km).
+//! The header is followed by `n m g h gdot hdot` lines.
+//!
+//! Blocks without secular variation are interpolated towards the next block,
+//! blocks with secular variation are extrapolated from their epoch.
+use std::io::Read;
+
+use crate::error::{parse_count, MAX_DEGREE};
+use crate::igrf::{IGRFresults, SecularVariation};
+use crate::{
+    math, CoordinateSystem, DateStatus, Extrapolation, FieldGradient, FieldResults, Generation,
+    GeomagError, GeomagneticModel, ModelDate, ModelSnapshot, ParseError, Provenance,
+};
+
+/// Header and coefficients of one block in a `.COF` file
+pub struct CofBlock {
+    /// Model name, e.g. `DGRF2000`
+    pub model: String,
+    /// Epoch in decimal years
+    pub epoch: f64,
+    /// Maximum degree of the main field
+    pub max1: usize,
+    /// Maximum degree of the secular variation
+    pub max2: usize,
+    /// Maximum degree of the acceleration
+    pub max3: usize,
+    /// First date (decimal years) the block is used for
+    pub yrmin: f64,
+    /// Last date (decimal years) the block is used for
+    pub yrmax: f64,
+    /// Lowest supported altitude (km)
+    pub altmin: f64,
+    /// Highest supported altitude (km)
+    pub altmax: f64,
+    main: Vec<f64>,
+    sv: Vec<f64>,
+}
+
+/// Multi-epoch model read from a `.COF` file
+pub struct CofModel {
+    name: &'static str,
+    blocks: Vec<CofBlock>,
+    extrapolation: Extrapolation,
+}
+
+fn parse_field<T: std::str::FromStr>(
+    chunks: &[&str],
+    i: usize,
+    what: &str,
+    line: usize,
+) -> Result<T, ParseError> {
+    chunks
+        .get(i)
+        .ok_or_else(|| ParseError::malformed(line, format!("missing {}", what)))?
+        .parse::<T>()
+        .map_err(|_| ParseError::malformed(line, format!("invalid {} '{}'", what, chunks[i])))
+}
+
+fn parse_header(chunks: &[&str], line: usize) -> Result<CofBlock, ParseError> {
+    let max1 = parse_field::<f64>(chunks, 2, "max1", line)?;
+    let max1 = parse_count(max1, "max1", 1, MAX_DEGREE, line)?;
+    let max2 = parse_field::<f64>(chunks, 3, "max2", line)?;
+    let max2 = parse_count(max2, "max2", 0, MAX_DEGREE, line)?;
+    let nmax = max1.max(max2);
+    Ok(CofBlock {
+        model: chunks[0].to_string(),
+        epoch: parse_field(chunks, 1, "epoch", line)?,
+        max1,
+        max2,
+        max3: parse_field(chunks, 4, "max3", line)?,
+        yrmin: parse_field(chunks, 5, "yrmin", line)?,
+        yrmax: parse_field(chunks, 6, "yrmax", line)?,
+        altmin: parse_field(chunks, 7, "altmin", line)?,
+        altmax: parse_field(chunks, 8, "altmax", line)?,
+        main: vec![0.; nmax * (nmax + 2)],
+        sv: vec![0.; nmax * (nmax + 2)],
+    })
+}
+
+fn parse_coeffs(block: &mut CofBlock, chunks: &[&str], line: usize) -> Result<(), ParseError> {
+    let n = parse_field::<usize>(chunks, 0, "degree", line)?;
+    let m = parse_field::<usize>(chunks, 1, "order", line)?;
+    let nmax = block.max1.max(block.max2);
+    if n == 0 || n > nmax || m > n {
+        return Err(ParseError::malformed(
+            line,
+            format!("degree/order {}/{} outside model degree {}", n, m, nmax),
+        ));
+    }
+    let g = parse_field::<f64>(chunks, 2, "g", line)?;
+    let h = parse_field::<f64>(chunks, 3, "h", line)?;
+    let gdot = parse_field::<f64>(chunks, 4, "gdot", line)?;
+    let hdot = parse_field::<f64>(chunks, 5, "hdot", line)?;
+
+    let i = math::gh_index(n, m);
+    block.main[i] = g;
+    block.sv[i] = gdot;
+    if m > 0 {
+        block.main[i + 1] = h;
+        block.sv[i + 1] = hdot;
+    }
+    Ok(())
+}
+
+impl CofModel {
+    /// Parses the contents of a `.COF` file
+    pub fn from_cof_str(cof: &str) -> Result<CofModel, ParseError> {
+        let mut blocks: Vec<CofBlock> = vec![];
+        for (i, text) in cof.lines().enumerate() {
+            let line = i + 1;
+            let chunks = text.split_whitespace().collect::<Vec<_>>();
+            if chunks.is_empty() {
+                continue;
+            }
+            let is_coeff_line = chunks[0].parse::<usize>().is_ok();
+            match blocks.last_mut() {
+                Some(block) if is_coeff_line => parse_coeffs(block, &chunks, line)?,
+                None if is_coeff_line => {
+                    return Err(ParseError::malformed(line, "coefficients before header"))
+                }
+                _ => blocks.push(parse_header(&chunks, line)?),
+            }
+        }
+        if blocks.is_empty() {
+            return Err(ParseError::malformed(1, "no model blocks found"));
+        }
+        // named after the newest block, e.g. IGRF2025 or WMM-2015
+        let name = crate::provenance::intern(&blocks.last().unwrap().model);
+        Ok(CofModel {
+            name,
+            blocks,
+            extrapolation: Extrapolation::Error,
+        })
+    }
+
+    /// Reads and parses a `.COF` file
+    pub fn from_cof_reader<R: Read>(mut reader: R) -> Result<CofModel, ParseError> {
+        let mut cof = String::new();
+        reader.read_to_string(&mut cof)?;
+        CofModel::from_cof_str(&cof)
+    }
+
+    /// Renames the model
+    pub fn with_name(mut self, name: &str) -> CofModel {
+        self.name = crate::provenance::intern(name);
+        self
+    }
+
+    /// Selects what happens to dates outside the validity, [`Extrapolation::Error`] by default
+    pub fn with_extrapolation(mut self, extrapolation: Extrapolation) -> CofModel {
+        self.extrapolation = extrapolation;
+        self
+    }
+
+    /// The blocks of the file, in file order
+    pub fn blocks(&self) -> &[CofBlock] {
+        &self.blocks
+    }
+
+    /// First and last date (decimal years) the model is valid for
+    pub fn validity(&self) -> (f64, f64) {
+        let min = self.blocks.iter().map(|b| b.yrmin).fold(f64::MAX, f64::min);
+        let max = self.blocks.iter().map(|b| b.yrmax).fold(f64::MIN, f64::max);
+        (min, max)
+    }
+
+    fn find_block(&self, date: f64) -> Result<usize, GeomagError> {
+        let (min, max) = self.validity();
+        self.blocks
+            .iter()
+            .position(|b| date >= b.yrmin && date < b.yrmax)
+            .or_else(|| self.blocks.iter().rposition(|b| date == b.yrmax))
+            .ok_or(GeomagError::DateOutOfRange { date, min, max })
+    }
+
+    // Coefficients of block `i` at `date`, similar to interpsh/extrapsh in geomag70
+    fn coeffs(&self, i: usize, date: f64) -> (Vec<f64>, usize) {
+        let block = &self.blocks[i];
+        match self.blocks.get(i + 1) {
+            Some(next) if block.max2 == 0 => {
+                let factor = (date - block.epoch) / (next.epoch - block.epoch);
+                let nmax = block.max1.max(next.max1);
+                // degrees only present in the first block are held constant
+                let k = next.max1 * (next.max1 + 2);
+                let coeffs = (0..nmax * (nmax + 2))
+                    .map(|j| {
+                        let start = block.main.get(j).copied().unwrap_or_default();
+                        let end = next.main.get(j).copied().unwrap_or_default();
+                        if j >= k {
+                            start
+                        } else {
+                            start + factor * (end - start)
+                        }
+                    })
+                    .collect();
+                (coeffs, nmax)
+            }
+            _ => {
+                let factor = date - block.epoch;
+                let coeffs = block
+                    .main
+                    .iter()
+                    .zip(block.sv.iter())
+                    .map(|(g, sv)| g + factor * sv)
+                    .collect();
+                (coeffs, block.max1.max(block.max2))
+            }
+        }
+    }
+
+    /// Interpolates the coefficients to `date` (decimal years or a [`ModelDate`]) for
+    /// evaluating many positions, panics if the date is outside the model, see [`CofModel::try_at`]
+    pub fn at(&self, date: impl Into<ModelDate>) -> ModelSnapshot {
+        self.try_at(date).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Interpolates the coefficients to `date`, returning an error outside the model validity
+    pub fn try_at(&self, date: impl Into<ModelDate>) -> Result<ModelSnapshot, GeomagError> {
+        let date = date.into().decimal_year();
+        self.extrapolation
+            .snapshot(self.validity(), date, |date| self.snapshot(date))
+    }
+
+    // Snapshot at a date within the validity
+    fn snapshot(&self, date: f64) -> Result<ModelSnapshot, GeomagError> {
+        let i = self.find_block(date)?;
+        let block = &self.blocks[i];
+        let (coeffs, nmax) = self.coeffs(i, date);
+        let (end_coeffs, _) = self.coeffs(i, date + 1.);
+        let (epochs, date_status) = match self.blocks.get(i + 1) {
+            _ if date == block.epoch && block.model.starts_with("DGRF") => {
+                ((date, date), DateStatus::Definitive)
+            }
+            Some(next) if block.max2 == 0 => ((block.epoch, next.epoch), DateStatus::Interpolated),
+            _ => ((block.epoch, block.yrmax), DateStatus::Predicted),
+        };
+        let provenance = Provenance::new(self.name, Generation::Custom, epochs, nmax, date_status);
+        Ok(ModelSnapshot::new(
+            date,
+            provenance,
+            self.reference_radius(),
+            (block.altmin, block.altmax),
+            coeffs,
+            end_coeffs,
+            SecularVariation::FiniteDifference,
+        ))
+    }
+
+    /// Calculates the field at geodetic `lat`/`lon` (degrees), `alt` km above the WGS84 ellipsoid
+    /// and `date` in decimal years or as a [`ModelDate`], annual change is calculated over the
+    /// following year. Panics if the input is invalid, see [`CofModel::try_calc`]
+    pub fn calc(&self, lat: f64, lon: f64, alt: f64, date: impl Into<ModelDate>) -> IGRFresults {
+        self.try_calc(lat, lon, alt, date)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Calculates the field, returning an error for input outside what the model supports
+    pub fn try_calc(
+        &self,
+        lat: f64,
+        lon: f64,
+        alt: f64,
+        date: impl Into<ModelDate>,
+    ) -> Result<IGRFresults, GeomagError> {
+        self.try_calc_in(CoordinateSystem::Geodetic, lat, lon, alt, date)
+    }
+
+    /// Calculates the field like [`CofModel::try_calc`] with the position given in `coords`,
+    /// `height` is altitude (km) for geodetic and radius (km) for geocentric input
+    pub fn try_calc_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: impl Into<ModelDate>,
+    ) -> Result<IGRFresults, GeomagError> {
+        self.try_at(date)?.try_calc_in(coords, lat, lon, height)
+    }
+
+    /// Spatial derivatives of the main field at the position given in `coords`,
+    /// see [`FieldGradient`]
+    pub fn try_gradient_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: impl Into<ModelDate>,
+    ) -> Result<FieldGradient, GeomagError> {
+        self.try_at(date)?.try_gradient_in(coords, lat, lon, height)
+    }
+}
+
+impl GeomagneticModel for CofModel {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn validity(&self) -> (f64, f64) {
+        CofModel::validity(self)
+    }
+
+    fn altitude_range(&self) -> (f64, f64) {
+        let min = self
+            .blocks
+            .iter()
+            .map(|b| b.altmin)
+            .fold(f64::MAX, f64::min);
+        let max = self
+            .blocks
+            .iter()
+            .map(|b| b.altmax)
+            .fold(f64::MIN, f64::max);
+        (min, max)
+    }
+
+    fn nmax(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|b| b.max1.max(b.max2))
+            .max()
+            .unwrap()
+    }
+
+    fn try_at(&self, date: f64) -> Result<ModelSnapshot, GeomagError> {
+        CofModel::try_at(self, date)
+    }
+
+    fn try_calc_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: f64,
+    ) -> Result<FieldResults, GeomagError> {
+        CofModel::try_calc_in(self, coords, lat, lon, height, date)
+    }
+
+    fn try_gradient_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: f64,
+    ) -> Result<FieldGradient, GeomagError> {
+        CofModel::try_gradient_in(self, coords, lat, lon, height, date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COF: &str = "\
+   IGRF95  1995.00 10  0  0 1995.00 2000.00   -1.0  600.0           IGRF95   0
+ 1  0 -29692.00      0.00      0.00      0.00                         IGRF95   1
+ 1  1  -1784.00   5306.00      0.00      0.00                         IGRF95   2
+   IGRF00  2000.00  1  1  0 2000.00 2005.00   -1.0  600.0           IGRF00   0
+ 1  0 -29619.40      0.00     10.00      0.00                         IGRF00   1
+ 1  1  -1728.20   5186.10      0.00      0.00                         IGRF00   2
+";
+
+    #[test]
+    fn parses_blocks() {
+        let model = CofModel::from_cof_str(COF).unwrap();
+        assert_eq!(model.blocks().len(), 2);
+        assert_eq!(model.blocks()[0].model, "IGRF95");
+        assert_eq!(model.blocks()[1].max2, 1);
+        assert_eq!(model.validity(), (1995., 2005.));
+    }
+
+    #[test]
+    fn rejects_huge_degree() {
+        let broken = COF.replace("IGRF00  2000.00  1  1", "IGRF00  2000.00 4000000000 0");
+        match CofModel::from_cof_str(&broken) {
+            Err(ParseError::Malformed { line, .. }) => assert_eq!(line, 4),
+            _ => panic!("expected malformed header"),
+        }
+    }
+
+    #[test]
+    fn reports_line_of_malformed_coefficient() {
+        let broken = COF.replace("-1728.20", "-17x8.20");
+        match CofModel::from_cof_str(&broken) {
+            Err(ParseError::Malformed { line, .. }) => assert_eq!(line, 6),
+            _ => panic!("expected malformed line"),
+        }
+    }
+
+    #[test]
+    fn rejects_degree_above_header() {
+        let broken = COF.replace(" 1  1  -1728.20", " 2  1  -1728.20");
+        assert!(matches!(
+            CofModel::from_cof_str(&broken),
+            Err(ParseError::Malformed { line: 6, .. })
+        ));
+    }
+
+    #[test]
+    fn enforces_year_and_altitude_ranges() {
+        let model = CofModel::from_cof_str(COF).unwrap();
+        assert!(model.try_calc(60., 10., 0., 2005.).is_ok());
+        assert_eq!(
+            model.try_calc(60., 10., 0., 2005.5).err(),
+            Some(GeomagError::DateOutOfRange {
+                date: 2005.5,
+                min: 1995.,
+                max: 2005.
+            })
+        );
+        assert!(matches!(
+            model.try_calc(60., 10., 601., 2000.),
+            Err(GeomagError::AltitudeOutOfRange { .. })
+        ));
+    }
+}
@@ -0,0 +1,424 @@
+//! Dates for evaluating the models, built from calendar dates, timestamps and GPS time.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::GeomagError;
+
+const SECONDS_PER_DAY: f64 = 86400.;
+// Julian date of 1970-01-01 00:00 UTC
+const UNIX_EPOCH_JD: f64 = 2440587.5;
+// Unix time of the GPS epoch 1980-01-06 00:00 UTC
+const GPS_EPOCH: f64 = 315964800.;
+const SECONDS_PER_WEEK: f64 = 7. * SECONDS_PER_DAY;
+// Dates from which GPS time is one more second ahead of UTC, 18 seconds since 2017
+const LEAP_SECONDS: [(i32, u32); 18] = [
+    (1981, 7),
+    (1982, 7),
+    (1983, 7),
+    (1985, 7),
+    (1988, 1),
+    (1990, 1),
+    (1991, 1),
+    (1992, 7),
+    (1993, 7),
+    (1994, 7),
+    (1996, 1),
+    (1997, 7),
+    (1999, 1),
+    (2006, 1),
+    (2009, 1),
+    (2012, 7),
+    (2015, 7),
+    (2017, 1),
+];
+
+/// A date (UTC) for evaluating a model.
+///
+/// Models are evaluated at decimal years where every calendar year counts as one, so
+/// 2024.5 is 00:00 on 2 July in the leap year 2024 and 2023.5 is noon on 2 July. All
+/// constructors convert the same way, so dates interpolate and extrapolate consistently
+/// whichever way they were given. Plain `f64` decimal years convert with `From`.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct ModelDate {
+    year: f64,
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days from 1970-01-01 to the given day of the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let (month, day) = (month as i64, day as i64);
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Year of the day `days` after 1970-01-01
+fn year_from_days(days: i64) -> i64 {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // the era starts in March, January and February belong to the next year
+    let march_based_month = (5 * day_of_year + 2) / 153;
+    year_of_era + era * 400 + (march_based_month >= 10) as i64
+}
+
+// Seconds from 1970-01-01 to the start of `year`. Whole 400 year cycles of the calendar
+// are counted in f64 so that extreme years lose precision instead of overflowing.
+fn year_start(year: f64) -> f64 {
+    let cycles = (year / 400.).floor();
+    let year_of_cycle = (year - cycles * 400.).clamp(0., 399.) as i64;
+    (cycles * 146097. + days_from_civil(year_of_cycle, 1, 1) as f64) * SECONDS_PER_DAY
+}
+
+// Year of the day `days` after 1970-01-01, counted in cycles as in `year_start`
+fn year_of_day(days: f64) -> f64 {
+    let cycles = (days / 146097.).floor();
+    let day_of_cycle = (days - cycles * 146097.).clamp(0., 146096.) as i64;
+    cycles * 400. + year_from_days(day_of_cycle) as f64
+}
+
+fn year_length(year: f64) -> f64 {
+    if is_leap_year(year.rem_euclid(400.) as i64) {
+        366. * SECONDS_PER_DAY
+    } else {
+        365. * SECONDS_PER_DAY
+    }
+}
+
+fn invalid(message: String) -> GeomagError {
+    GeomagError::InvalidDate(message)
+}
+
+impl ModelDate {
+    /// Decimal year where every calendar year counts as one
+    pub fn from_decimal_year(year: f64) -> ModelDate {
+        ModelDate { year }
+    }
+
+    /// Calendar date at 00:00 UTC
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Result<ModelDate, GeomagError> {
+        ModelDate::from_ymd_hms(year, month, day, 0, 0, 0.)
+    }
+
+    /// Calendar date and time of day (UTC), `second` may be fractional
+    pub fn from_ymd_hms(
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+        second: f64,
+    ) -> Result<ModelDate, GeomagError> {
+        if !(1..=12).contains(&month) || !(1..=days_in_month(year as i64, month)).contains(&day) {
+            return Err(invalid(format!("{}-{:02}-{:02}", year, month, day)));
+        }
+        // a leap second at the end of a day counts as the first second of the next
+        if hour > 23 || minute > 59 || !(0. ..61.).contains(&second) {
+            return Err(invalid(format!("{:02}:{:02}:{}", hour, minute, second)));
+        }
+        let days = days_from_civil(year as i64, month, day);
+        let seconds = (hour * 3600 + minute * 60) as f64 + second;
+        Ok(ModelDate::from_unix(
+            days as f64 * SECONDS_PER_DAY + seconds,
+        ))
+    }
+
+    /// Fractional day of the year, 1.0 is 00:00 UTC on 1 January and 1.5 is noon that day
+    pub fn from_day_of_year(year: i32, day: f64) -> Result<ModelDate, GeomagError> {
+        let days = year_length(year as f64) / SECONDS_PER_DAY;
+        if !(1. ..days + 1.).contains(&day) {
+            return Err(invalid(format!("day {} of {}", day, year)));
+        }
+        let seconds = year_start(year as f64) + (day - 1.) * SECONDS_PER_DAY;
+        Ok(ModelDate::from_unix(seconds))
+    }
+
+    /// Seconds since 1970-01-01 00:00 UTC, ignoring leap seconds as Unix time does
+    pub fn from_unix(seconds: f64) -> ModelDate {
+        if !seconds.is_finite() {
+            return ModelDate { year: seconds };
+        }
+        let year = year_of_day((seconds / SECONDS_PER_DAY).floor());
+        ModelDate {
+            year: year + (seconds - year_start(year)) / year_length(year),
+        }
+    }
+
+    /// Julian date (UTC), e.g. 2451545.0 for 2000-01-01 12:00
+    pub fn from_julian_date(jd: f64) -> ModelDate {
+        ModelDate::from_unix((jd - UNIX_EPOCH_JD) * SECONDS_PER_DAY)
+    }
+
+    /// GPS week, counted from 1980-01-06 without rollover, and seconds into the week.
+    /// GPS time runs ahead of UTC by the leap seconds since 1980, which are removed.
+    pub fn from_gps(week: u32, seconds: f64) -> ModelDate {
+        let gps = GPS_EPOCH + week as f64 * SECONDS_PER_WEEK + seconds;
+        // a leap second applies once UTC, which is behind GPS by the earlier leap seconds,
+        // reaches its date
+        let leap = LEAP_SECONDS
+            .iter()
+            .enumerate()
+            .take_while(|(i, &(year, month))| {
+                let utc = days_from_civil(year as i64, month, 1) as f64 * SECONDS_PER_DAY;
+                gps - (*i as f64 + 1.) >= utc
+            })
+            .count();
+        ModelDate::from_unix(gps - leap as f64)
+    }
+
+    /// The date as a decimal year, as taken by the models
+    pub fn decimal_year(self) -> f64 {
+        self.year
+    }
+
+    /// Seconds since 1970-01-01 00:00 UTC
+    pub fn unix(self) -> f64 {
+        if !self.year.is_finite() {
+            return self.year;
+        }
+        let year = self.year.floor();
+        year_start(year) + (self.year - year) * year_length(year)
+    }
+
+    /// Julian date (UTC)
+    pub fn julian_date(self) -> f64 {
+        self.unix() / SECONDS_PER_DAY + UNIX_EPOCH_JD
+    }
+}
+
+impl From<f64> for ModelDate {
+    fn from(year: f64) -> ModelDate {
+        ModelDate::from_decimal_year(year)
+    }
+}
+
+impl From<ModelDate> for f64 {
+    fn from(date: ModelDate) -> f64 {
+        date.decimal_year()
+    }
+}
+
+impl fmt::Display for ModelDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.year)
+    }
+}
+
+fn number<T: FromStr>(s: &str, text: &str) -> Result<T, GeomagError> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+        return Err(invalid(text.to_string()));
+    }
+    s.parse::<T>().map_err(|_| invalid(text.to_string()))
+}
+
+// hh[:mm[:ss[.fff]]] to seconds, `text` is the whole input for errors
+fn time_of_day(time: &str, text: &str) -> Result<(u32, u32, f64), GeomagError> {
+    let parts = time.split(':').collect::<Vec<_>>();
+    match parts.as_slice() {
+        [h] => Ok((number(h, text)?, 0, 0.)),
+        [h, m] => Ok((number(h, text)?, number(m, text)?, 0.)),
+        [h, m, s] => Ok((number(h, text)?, number(m, text)?, number(s, text)?)),
+        _ => Err(invalid(text.to_string())),
+    }
+}
+
+// Offset from UTC in seconds of Z, +hh, +hh:mm or +hhmm
+fn utc_offset(offset: &str, text: &str) -> Result<f64, GeomagError> {
+    if offset == "Z" || offset.is_empty() {
+        return Ok(0.);
+    }
+    let sign = match offset.as_bytes()[0] {
+        b'+' => 1.,
+        b'-' => -1.,
+        _ => return Err(invalid(text.to_string())),
+    };
+    let digits = offset[1..].replace(':', "");
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid(text.to_string()));
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (number::<u32>(&digits, text)?, 0),
+        4 => (number(&digits[..2], text)?, number(&digits[2..], text)?),
+        _ => return Err(invalid(text.to_string())),
+    };
+    if hours > 23 || minutes > 59 {
+        return Err(invalid(text.to_string()));
+    }
+    Ok(sign * (hours * 3600 + minutes * 60) as f64)
+}
+
+impl FromStr for ModelDate {
+    type Err = GeomagError;
+
+    /// ISO 8601 date `2024-03-01` or ordinal date `2024-061`, optionally followed by `T` or
+    /// a space and the time `hh:mm:ss.fff`, `hh:mm` or `hh` with an offset `Z`, `+hh:mm`,
+    /// `-hhmm` or `+hh`. Times without an offset are UTC.
+    fn from_str(text: &str) -> Result<ModelDate, GeomagError> {
+        let text = text.trim();
+        let (date, time) = match text.split_once(['T', ' ']) {
+            Some((date, time)) => (date, Some(time)),
+            None => (text, None),
+        };
+        let (time, offset) = match time {
+            Some(time) => match time.find(['Z', '+', '-']) {
+                Some(i) => (Some(&time[..i]), utc_offset(&time[i..], text)?),
+                None => (Some(time), 0.),
+            },
+            None => (None, 0.),
+        };
+        let (hour, minute, second) = match time {
+            Some(time) => time_of_day(time, text)?,
+            None => (0, 0, 0.),
+        };
+        let parts = date.split('-').collect::<Vec<_>>();
+        let midnight = match parts.as_slice() {
+            [year, month, day] if year.len() == 4 => {
+                let (year, month, day) = (
+                    number(year, text)?,
+                    number(month, text)?,
+                    number(day, text)?,
+                );
+                ModelDate::from_ymd(year, month, day)?
+            }
+            [year, day] if year.len() == 4 && day.len() == 3 => {
+                ModelDate::from_day_of_year(number(year, text)?, number::<u32>(day, text)? as f64)?
+            }
+            _ => return Err(invalid(text.to_string())),
+        };
+        // checks the time of day
+        ModelDate::from_ymd_hms(2000, 1, 1, hour, minute, second)?;
+        let seconds = (hour * 3600 + minute * 60) as f64 + second - offset;
+        Ok(ModelDate::from_unix(midnight.unix() + seconds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: ModelDate, b: f64) -> bool {
+        (a.decimal_year() - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn calendar_dates() {
+        assert_eq!(
+            ModelDate::from_ymd(2024, 1, 1).unwrap().decimal_year(),
+            2024.
+        );
+        assert!(close(
+            ModelDate::from_ymd_hms(2024, 7, 2, 0, 0, 0.).unwrap(),
+            2024.5
+        ));
+        assert!(close(
+            ModelDate::from_ymd_hms(2023, 7, 2, 12, 0, 0.).unwrap(),
+            2023.5
+        ));
+        assert!(close(
+            ModelDate::from_ymd(1900, 3, 1).unwrap(),
+            1900. + 59. / 365.
+        ));
+        assert!(close(
+            ModelDate::from_day_of_year(2020, 366.5).unwrap(),
+            2020. + 365.5 / 366.
+        ));
+        assert!(close(
+            ModelDate::from_day_of_year(2021, 183.5).unwrap(),
+            2021.5
+        ));
+        assert!(ModelDate::from_ymd(2023, 2, 29).is_err());
+        assert!(ModelDate::from_ymd(2024, 13, 1).is_err());
+        assert!(ModelDate::from_ymd_hms(2024, 1, 1, 24, 0, 0.).is_err());
+        assert!(ModelDate::from_day_of_year(2023, 366.).is_err());
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(ModelDate::from_unix(0.).decimal_year(), 1970.);
+        assert!((ModelDate::from_unix(-86400.).unix() + 86400.).abs() < 1e-5);
+        assert!(close(ModelDate::from_unix(1719878400.), 2024.5));
+        assert!(close(
+            ModelDate::from_julian_date(2451545.),
+            2000. + 0.5 / 366.
+        ));
+        assert_eq!(
+            ModelDate::from_decimal_year(2024.5).julian_date(),
+            2460493.5
+        );
+        // 2024-07-02 00:00 UTC is GPS week 2321, 18 leap seconds later in the week
+        let week_start = days_from_civil(2024, 6, 30) as f64 * SECONDS_PER_DAY - GPS_EPOCH;
+        assert_eq!(week_start, 2321. * SECONDS_PER_WEEK);
+        assert!(close(ModelDate::from_gps(2321, 172818.), 2024.5));
+        assert!((ModelDate::from_gps(0, 0.).unix() - GPS_EPOCH).abs() < 1e-5);
+        for year in [1600, 1899, 1900, 1970, 2000, 2023, 2024, 2100] {
+            assert_eq!(year_from_days(days_from_civil(year, 1, 1)), year);
+            assert_eq!(year_from_days(days_from_civil(year, 12, 31)), year);
+        }
+    }
+
+    #[test]
+    fn extreme_dates() {
+        for year in [-123456.25, 1e6 + 0.5, 3e9 + 0.75] {
+            let date = ModelDate::from_decimal_year(year);
+            assert!((ModelDate::from_unix(date.unix()).decimal_year() - year).abs() < 1e-3);
+        }
+        assert_eq!(
+            year_start(-400.),
+            days_from_civil(-400, 1, 1) as f64 * SECONDS_PER_DAY
+        );
+        assert!(ModelDate::from_unix(1e25).decimal_year() > 3e17);
+        assert!(ModelDate::from_unix(-1e25).decimal_year() < -3e17);
+        assert!(ModelDate::from_decimal_year(1e20).julian_date() > 3e22);
+        assert!(ModelDate::from_decimal_year(-1e20).julian_date() < -3e22);
+        let (lat, lon) = crate::mlt::subsolar_point(1e20);
+        assert!(lat.abs() <= 90. && lon.abs() <= 180.);
+    }
+
+    #[test]
+    fn iso_8601() {
+        let parse = |s: &str| s.parse::<ModelDate>().map(|d| d.decimal_year());
+        assert_eq!(parse("2024-01-01"), Ok(2024.));
+        assert_eq!(parse("2024-001"), Ok(2024.));
+        assert!(close("2024-07-02T00:00:00Z".parse().unwrap(), 2024.5));
+        assert!(close("2024-07-02 02:00+02:00".parse().unwrap(), 2024.5));
+        assert!(close("2024-07-01T23:30:00.0-0030".parse().unwrap(), 2024.5));
+        assert!(close("2024-184T00".parse().unwrap(), 2024.5));
+        for bad in [
+            "2024",
+            "2024-02-30",
+            "24-01-01",
+            "2024-01-01T25:00",
+            "2024-01-01T1:2:3:4",
+            "2024-01-01T00+1é1",
+            "2024-01-01T00++5",
+            "2024-01-01T00Z05",
+            "2024-01-01T00+24",
+            "2024-01-01T00+01:60",
+            "x",
+        ] {
+            assert!(
+                matches!(parse(bad), Err(GeomagError::InvalidDate(_))),
+                "{}",
+                bad
+            );
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! The centred and eccentric dipoles of a model and geomagnetic coordinates.
+use crate::math::{self, EARTHS_RADIUS};
+use crate::{CoordinateSystem, MagneticComponents, ModelSnapshot};
+
+/// The dipole at the centre of the Earth given by the degree 1 coefficients.
+///
+/// Geomagnetic coordinates have their north pole at the northern pole of the dipole axis
+/// and longitude 0 on the meridian through the geographic south pole, latitudes and
+/// longitudes are geocentric in degrees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dipole {
+    g10: f64,
+    g11: f64,
+    h11: f64,
+    // geographic directions of the geomagnetic x, y and z axes
+    axes: [[f64; 3]; 3],
+}
+
+impl Dipole {
+    /// Dipole of the Gauss coefficients g(1,0), g(1,1) and h(1,1) (nT)
+    pub fn new(g10: f64, g11: f64, h11: f64) -> Dipole {
+        // the field points down at the northern pole, opposite the dipole moment
+        let b0 = (g10 * g10 + g11 * g11 + h11 * h11).sqrt();
+        let z = [-g11 / b0, -h11 / b0, -g10 / b0];
+        // y is perpendicular to both rotation and dipole axis
+        let y = math::cross([0., 0., 1.], z);
+        let y = match math::norm(y) {
+            n if n > 1e-12 => y.map(|c| c / n),
+            _ => [0., 1., 0.],
+        };
+        let x = math::cross(y, z);
+        Dipole {
+            g10,
+            g11,
+            h11,
+            axes: [x, y, z],
+        }
+    }
+
+    /// The coefficients g(1,0), g(1,1) and h(1,1) (nT)
+    pub fn coefficients(&self) -> (f64, f64, f64) {
+        (self.g10, self.g11, self.h11)
+    }
+
+    /// Field strength (nT) at the geomagnetic equator on the reference sphere
+    pub fn field_strength(&self) -> f64 {
+        (self.g10 * self.g10 + self.g11 * self.g11 + self.h11 * self.h11).sqrt()
+    }
+
+    /// Magnetic moment (A m²)
+    pub fn moment(&self) -> f64 {
+        // 4π a³ B0 / μ0 with a in m and B0 in T
+        EARTHS_RADIUS.powi(3) * self.field_strength() * 1e7
+    }
+
+    /// Angle (degrees) between the dipole axis and the rotation axis
+    pub fn tilt(&self) -> f64 {
+        self.axes[2][2].clamp(-1., 1.).acos().to_degrees()
+    }
+
+    /// Latitude and longitude of the geomagnetic north pole, where the dipole axis leaves the
+    /// northern hemisphere
+    pub fn north_pole(&self) -> (f64, f64) {
+        math::spherical(self.axes[2])
+    }
+
+    /// Latitude and longitude of the geomagnetic south pole
+    pub fn south_pole(&self) -> (f64, f64) {
+        math::spherical(self.axes[2].map(|c| -c))
+    }
+
+    /// Geomagnetic latitude and longitude of geocentric `lat`/`lon`
+    pub fn to_geomagnetic(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let v = math::cartesian(lat, lon);
+        math::spherical(self.axes.map(|axis| math::dot(axis, v)))
+    }
+
+    /// Geocentric latitude and longitude of geomagnetic `lat`/`lon`
+    pub fn from_geomagnetic(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let v = math::cartesian(lat, lon);
+        let [x, y, z] = self.axes;
+        math::spherical([0, 1, 2].map(|i| x[i] * v[0] + y[i] * v[1] + z[i] * v[2]))
+    }
+}
+
+/// The dipole offset from the centre of the Earth that best fits the degree 1 and 2
+/// coefficients, after Fraser-Smith (1987).
+///
+/// It has the axis and moment of the centred [`Dipole`]. Eccentric coordinates are
+/// geomagnetic coordinates about its centre, with the distance (km) from the centre.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EccentricDipole {
+    dipole: Dipole,
+    // geocentric cartesian position (km) of the centre, z along the rotation axis
+    center: [f64; 3],
+}
+
+impl EccentricDipole {
+    /// Eccentric dipole of the Gauss coefficients (nT) g(1,0), g(1,1), h(1,1), g(2,0),
+    /// g(2,1), h(2,1), g(2,2) and h(2,2)
+    pub fn new(gh: [f64; 8]) -> EccentricDipole {
+        let [g10, g11, h11, g20, g21, h21, g22, h22] = gh;
+        let dipole = Dipole::new(g10, g11, h11);
+        let b2 = g10 * g10 + g11 * g11 + h11 * h11;
+        let s3 = 3f64.sqrt();
+        let l0 = 2. * g10 * g20 + s3 * (g11 * g21 + h11 * h21);
+        let l1 = -g11 * g20 + s3 * (g10 * g21 + g11 * g22 + h11 * h22);
+        let l2 = -h11 * g20 + s3 * (g10 * h21 - h11 * g22 + g11 * h22);
+        let e = (l0 * g10 + l1 * g11 + l2 * h11) / (4. * b2);
+        let offset = |l: f64, g: f64| EARTHS_RADIUS * (l - g * e) / (3. * b2);
+        EccentricDipole {
+            dipole,
+            center: [offset(l1, g11), offset(l2, h11), offset(l0, g10)],
+        }
+    }
+
+    /// The centred dipole with the same axis and moment
+    pub fn dipole(&self) -> Dipole {
+        self.dipole
+    }
+
+    /// Geocentric cartesian position (km) of the centre, x through longitude 0 and z along
+    /// the rotation axis
+    pub fn center(&self) -> [f64; 3] {
+        self.center
+    }
+
+    /// Geocentric latitude and longitude (degrees) of the direction of the centre and its
+    /// distance (km) from the centre of the Earth
+    pub fn offset(&self) -> (f64, f64, f64) {
+        let (lat, lon) = math::spherical(self.center);
+        (lat, lon, math::norm(self.center))
+    }
+
+    /// Latitude and longitude of the eccentric north pole, where the axis through the
+    /// centre leaves the reference sphere in the north
+    pub fn north_pole(&self) -> (f64, f64) {
+        self.axis_exit(self.dipole.axes[2])
+    }
+
+    /// Latitude and longitude of the eccentric south pole
+    pub fn south_pole(&self) -> (f64, f64) {
+        self.axis_exit(self.dipole.axes[2].map(|c| -c))
+    }
+
+    // Where the ray from the centre along `direction` crosses the reference sphere
+    fn axis_exit(&self, direction: [f64; 3]) -> (f64, f64) {
+        let c = self.center;
+        let along = math::dot(c, direction);
+        let t = -along + (along * along - math::dot(c, c) + EARTHS_RADIUS * EARTHS_RADIUS).sqrt();
+        math::spherical([0, 1, 2].map(|i| c[i] + t * direction[i]))
+    }
+
+    /// Eccentric latitude, longitude (degrees) and distance (km) from the centre of the
+    /// point at geocentric `lat`/`lon` and `radius` (km)
+    pub fn to_eccentric(&self, lat: f64, lon: f64, radius: f64) -> (f64, f64, f64) {
+        let v = self.relative(lat, lon, radius);
+        let (mlat, mlon) = math::spherical(self.dipole.axes.map(|axis| math::dot(axis, v)));
+        (mlat, mlon, math::norm(v))
+    }
+
+    /// Geocentric latitude, longitude (degrees) and radius (km) of the point at eccentric
+    /// `lat`/`lon` and `distance` (km) from the centre
+    pub fn from_eccentric(&self, lat: f64, lon: f64, distance: f64) -> (f64, f64, f64) {
+        let v = math::cartesian(lat, lon);
+        let [x, y, z] = self.dipole.axes;
+        let p = [0, 1, 2]
+            .map(|i| self.center[i] + distance * (x[i] * v[0] + y[i] * v[1] + z[i] * v[2]));
+        let (lat, lon) = math::spherical(p);
+        (lat, lon, math::norm(p))
+    }
+
+    // Position (km) of geocentric `lat`/`lon` and `radius` relative to the centre
+    fn relative(&self, lat: f64, lon: f64, radius: f64) -> [f64; 3] {
+        let p = math::cartesian(lat, lon);
+        [0, 1, 2].map(|i| radius * p[i] - self.center[i])
+    }
+
+    /// Approximates the main field at geodetic `lat`/`lon` (degrees) and `alt` km above the
+    /// WGS84 ellipsoid by the field of the eccentric dipole
+    pub fn calc(&self, lat: f64, lon: f64, alt: f64) -> MagneticComponents {
+        self.calc_in(CoordinateSystem::Geodetic, lat, lon, alt)
+    }
+
+    /// Approximates the main field with the position given in `coords`, `height` is
+    /// altitude (km) for geodetic and radius (km) for geocentric input. Much cheaper than
+    /// the full model but rough, at the surface typically 10000 nT off near the poles and
+    /// in the South Atlantic.
+    pub fn calc_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+    ) -> MagneticComponents {
+        let pos = math::Position::new(lat, lon, height, coords);
+        let (slon, clon) = pos.lon.sin_cos();
+        let up = [pos.clat * clon, pos.clat * slon, pos.slat];
+        let north = [-pos.slat * clon, -pos.slat * slon, pos.clat];
+        let east = [-slon, clon, 0.];
+        // B = a³ B0 (3 (m·r) r - m) / |r|³ with the moment m opposite the axis
+        let r = [0, 1, 2].map(|i| pos.r * up[i] - self.center[i]);
+        let distance = math::norm(r);
+        let r = r.map(|c| c / distance);
+        let m = self.dipole.axes[2].map(|c| -c);
+        let scale = self.dipole.field_strength() * (EARTHS_RADIUS / distance).powi(3);
+        let along = 3. * math::dot(m, r);
+        let b = [0, 1, 2].map(|i| scale * (along * r[i] - m[i]));
+        let field = pos.field((math::dot(b, north), math::dot(b, east), -math::dot(b, up)));
+        let dif = math::Difh::from_orthognal_strength(&field);
+        MagneticComponents {
+            declination: dif.declination.to_degrees(),
+            inclination: dif.inclination.to_degrees(),
+            horizontal_intensity: dif.horizontal_intensity,
+            orthogonal_strength: field,
+            total_intensity: dif.total_intensity,
+        }
+    }
+}
+
+impl ModelSnapshot {
+    /// The centred dipole at the date of the snapshot
+    pub fn dipole(&self) -> Dipole {
+        let g = |n, m| self.coeffs[math::gh_index(n, m)];
+        Dipole::new(g(1, 0), g(1, 1), self.coeffs[math::gh_index(1, 1) + 1])
+    }
+
+    /// The eccentric dipole at the date of the snapshot, centred when the coefficients stop
+    /// at degree 1
+    pub fn eccentric_dipole(&self) -> EccentricDipole {
+        let mut gh = [0.; 8];
+        let n = self.coeffs.len().min(8);
+        gh[..n].copy_from_slice(&self.coeffs[..n]);
+        EccentricDipole::new(gh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axial_dipole() {
+        let dipole = Dipole::new(-30000., 0., 0.);
+        assert_eq!(dipole.tilt(), 0.);
+        assert_eq!(dipole.north_pole().0, 90.);
+        let (lat, lon) = dipole.to_geomagnetic(45., 30.);
+        assert!((lat - 45.).abs() < 1e-12 && (lon - 30.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn centred_eccentric_dipole() {
+        let dipole = EccentricDipole::new([-30000., 0., 0., 0., 0., 0., 0., 0.]);
+        assert_eq!(dipole.offset().2, 0.);
+        let equator = dipole.calc_in(CoordinateSystem::Geocentric, 0., 30., EARTHS_RADIUS);
+        assert!((equator.orthogonal_strength.north - 30000.).abs() < 1e-9);
+        assert!(equator.orthogonal_strength.down.abs() < 1e-9);
+        let pole = dipole.calc_in(CoordinateSystem::Geocentric, 89.9, 0., 2. * EARTHS_RADIUS);
+        assert!((pole.total_intensity - 7500.).abs() < 1.);
+        assert!(pole.inclination > 89.);
+
+        let snapshot = ModelSnapshot::from_coefficients(
+            2020.,
+            1,
+            EARTHS_RADIUS,
+            (-1., 1000.),
+            vec![-30000., 1000., 2000.],
+            vec![0.; 3],
+        )
+        .unwrap();
+        let eccentric = snapshot.eccentric_dipole();
+        assert_eq!(eccentric.offset().2, 0.);
+        assert_eq!(eccentric.dipole(), snapshot.dipole());
+    }
+
+    #[test]
+    fn coordinates_round_trip() {
+        let dipole = Dipole::new(-29404.8, -1450.9, 4652.5);
+        for (lat, lon) in [(0., 0.), (45., -120.), (-60., 170.), (89., 10.)] {
+            let (mlat, mlon) = dipole.to_geomagnetic(lat, lon);
+            let (back_lat, back_lon) = dipole.from_geomagnetic(mlat, mlon);
+            assert!((back_lat - lat).abs() < 1e-9);
+            assert!((back_lon - lon).abs() < 1e-9);
+        }
+        let (lat, lon) = dipole.north_pole();
+        assert!((dipole.to_geomagnetic(lat, lon).0 - 90.).abs() < 1e-9);
+        // the geographic poles are on the meridians 0 and 180
+        let (mlat, mlon) = dipole.to_geomagnetic(-90., 0.);
+        assert!(mlon.abs() < 1e-9);
+        assert!((mlat + 90. - dipole.tilt()).abs() < 1e-9);
+        assert!((dipole.to_geomagnetic(90., 0.).1.abs() - 180.).abs() < 1e-9);
+
+        let eccentric = EccentricDipole::new([
+            -29404.8, -1450.9, 4652.5, -2499.6, 2982.0, -2991.6, 1677.0, -734.6,
+        ]);
+        let (mlat, mlon, distance) = eccentric.to_eccentric(45., -120., 7000.);
+        let (lat, lon, r) = eccentric.from_eccentric(mlat, mlon, distance);
+        assert!((lat - 45.).abs() < 1e-9 && (lon + 120.).abs() < 1e-9);
+        assert!((r - 7000.).abs() < 1e-9);
+        // the poles are on the reference sphere, on the axis through the centre
+        for (lat, lon) in [eccentric.north_pole(), eccentric.south_pole()] {
+            let (mlat, _, distance) = eccentric.to_eccentric(lat, lon, EARTHS_RADIUS);
+            assert!((mlat.abs() - 90.).abs() < 1e-6);
+            assert!(distance < EARTHS_RADIUS + eccentric.offset().2);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+
+use crate::{math, CoordinateSystem};
+
+/// Error returned when a coefficient file can not be read
+#[derive(Debug)]
+pub enum ParseError {
+    /// The underlying reader failed
+    Io(std::io::Error),
+    /// A line of the file is malformed, `line` is 1-based
+    Malformed { line: usize, message: String },
+}
+
+impl ParseError {
+    pub(crate) fn malformed(line: usize, message: impl Into<String>) -> Self {
+        ParseError::Malformed {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+// Highest degree accepted from a coefficient file, well above the crustal models
+// (EMM goes to 790) while keeping a bogus header from allocating gigabytes
+pub(crate) const MAX_DEGREE: usize = 2000;
+
+// Whole number in a header field at least `min` and at most `max`
+pub(crate) fn parse_count(
+    value: f64,
+    name: &str,
+    min: usize,
+    max: usize,
+    line: usize,
+) -> Result<usize, ParseError> {
+    if value.fract() != 0. || !(min as f64..=max as f64).contains(&value) {
+        return Err(ParseError::malformed(
+            line,
+            format!(
+                "{} {} is not a whole number from {} to {}",
+                name, value, min, max
+            ),
+        ));
+    }
+    Ok(value as usize)
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(err) => write!(f, "failed to read coefficients: {}", err),
+            ParseError::Malformed { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(err) => Some(err),
+            ParseError::Malformed { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ParseError {
+    fn from(err: std::io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+/// Error returned when a model can not be evaluated for the given input
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeomagError {
+    /// Date (decimal years) is outside the validity of the model
+    DateOutOfRange { date: f64, min: f64, max: f64 },
+    /// Latitude (degrees) is outside -90 to 90
+    LatitudeOutOfRange(f64),
+    /// Longitude (degrees) is outside -180 to 360
+    LongitudeOutOfRange(f64),
+    /// One of the inputs is NaN or infinite
+    NonFinite,
+    /// Altitude (km) is outside what the model supports
+    AltitudeOutOfRange { alt: f64, min: f64, max: f64 },
+    /// Coefficients of two epochs can not be combined, e.g. extrapolating
+    /// from an epoch of lower degree to one of higher degree
+    NmaxMismatch {
+        start_epoch: f64,
+        start_nmax: usize,
+        end_epoch: f64,
+        end_nmax: usize,
+    },
+    /// Output buffer of a batch evaluation holds `len` results where `needed` are required
+    OutputTooSmall { len: usize, needed: usize },
+    /// A date can not be read or does not exist, e.g. 2023-02-29
+    InvalidDate(String),
+    /// An iterative search, e.g. for a dip pole, did not settle within its iteration limit
+    NotConverged,
+    /// Tolerance (km) or longest step (km) of a field line trace is not a positive finite
+    /// number, see [`TraceOptions::with_accuracy`](crate::TraceOptions::with_accuracy)
+    InvalidAccuracy { tolerance: f64, max_step: f64 },
+    /// Coefficients or parameters given for a model do not fit together, e.g. fewer
+    /// coefficients than the degree needs
+    InvalidModel(String),
+}
+
+impl fmt::Display for GeomagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeomagError::DateOutOfRange { date, min, max } => {
+                write!(f, "date {} outside model validity {} - {}", date, min, max)
+            }
+            GeomagError::LatitudeOutOfRange(lat) => {
+                write!(f, "latitude {} outside -90 - 90 degrees", lat)
+            }
+            GeomagError::LongitudeOutOfRange(lon) => {
+                write!(f, "longitude {} outside -180 - 360 degrees", lon)
+            }
+            GeomagError::NonFinite => write!(f, "input is not a finite number"),
+            GeomagError::AltitudeOutOfRange { alt, min, max } => {
+                write!(
+                    f,
+                    "altitude {} km outside model range {} - {} km",
+                    alt, min, max
+                )
+            }
+            GeomagError::NmaxMismatch {
+                start_epoch,
+                start_nmax,
+                end_epoch,
+                end_nmax,
+            } => write!(
+                f,
+                "can not combine degree {} coefficients of {} with degree {} coefficients of {}",
+                start_nmax, start_epoch, end_nmax, end_epoch
+            ),
+            GeomagError::OutputTooSmall { len, needed } => {
+                write!(f, "output buffer holds {} results, {} needed", len, needed)
+            }
+            GeomagError::InvalidDate(date) => write!(f, "invalid date `{}`", date),
+            GeomagError::NotConverged => write!(f, "iteration did not converge"),
+            GeomagError::InvalidAccuracy {
+                tolerance,
+                max_step,
+            } => write!(
+                f,
+                "tolerance {} km and longest step {} km must be positive",
+                tolerance, max_step
+            ),
+            GeomagError::InvalidModel(message) => write!(f, "invalid model: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for GeomagError {}
+
+// Checks position and date passed to a model, `altitude` is the (min, max) supported in km.
+// For geocentric input `height` is a radius and checked as altitude above the ellipsoid.
+pub(crate) fn check_input(
+    coords: CoordinateSystem,
+    lat: f64,
+    lon: f64,
+    height: f64,
+    date: f64,
+    altitude: (f64, f64),
+) -> Result<(), GeomagError> {
+    if !(lat.is_finite() && lon.is_finite() && height.is_finite() && date.is_finite()) {
+        return Err(GeomagError::NonFinite);
+    }
+    if !(-90. ..=90.).contains(&lat) {
+        return Err(GeomagError::LatitudeOutOfRange(lat));
+    }
+    if !(-180. ..=360.).contains(&lon) {
+        return Err(GeomagError::LongitudeOutOfRange(lon));
+    }
+    let alt = match coords {
+        CoordinateSystem::Geodetic => height,
+        CoordinateSystem::Geocentric => math::geocentric_altitude(lat, height),
+    };
+    let (min, max) = altitude;
+    if !(min..=max).contains(&alt) {
+        return Err(GeomagError::AltitudeOutOfRange { alt, min, max });
+    }
+    Ok(())
+}
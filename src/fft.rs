@@ -0,0 +1,167 @@
+// Mixed radix FFT of any length for synthesising the field along circles of latitude.
+//
+// Lengths are split into their prime factors, smallest first, and transformed with
+// recursive decimation in time. Typical grid sizes such as 360 or 1440 only have small
+// factors, a large prime factor falls back to a direct sum over that factor.
+use std::f64::consts::PI;
+use std::ops::{Add, Mul};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Complex {
+    pub(crate) re: f64,
+    pub(crate) im: f64,
+}
+
+impl Complex {
+    pub(crate) fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    pub(crate) fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    // e^(i angle)
+    pub(crate) fn cis(angle: f64) -> Complex {
+        let (s, c) = angle.sin_cos();
+        Complex::new(c, s)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: f64) -> Complex {
+        Complex::new(self.re * other, self.im * other)
+    }
+}
+
+// Inverse transform of a fixed length, out[k] = sum over j of input[j] e^(2 pi i j k / n)
+// without scaling
+pub(crate) struct Fft {
+    factors: Vec<usize>,
+    // e^(2 pi i k / n) for k = 0..n
+    twiddles: Vec<Complex>,
+}
+
+impl Fft {
+    pub(crate) fn new(n: usize) -> Fft {
+        let mut factors = vec![];
+        let mut rest = n;
+        let mut p = 2;
+        while rest > 1 {
+            if p * p > rest {
+                factors.push(rest);
+                break;
+            }
+            if rest.is_multiple_of(p) {
+                factors.push(p);
+                rest /= p;
+            } else {
+                p += 1;
+            }
+        }
+        let twiddles = (0..n)
+            .map(|k| Complex::cis(2. * PI * k as f64 / n as f64))
+            .collect();
+        Fft { factors, twiddles }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.twiddles.len()
+    }
+
+    // Values of scratch space `inverse` needs, the largest factor of the length
+    pub(crate) fn scratch_len(&self) -> usize {
+        self.factors.iter().max().copied().unwrap_or(1)
+    }
+
+    // `scratch` holds at least `scratch_len()` values so the caller can reuse it
+    pub(crate) fn inverse(&self, input: &[Complex], out: &mut [Complex], scratch: &mut [Complex]) {
+        assert!(input.len() == self.len() && out.len() == self.len());
+        self.transform(input, 1, out, &self.factors, scratch);
+    }
+
+    // Transforms input[0], input[stride], ... into `out`, `factors` are those of out.len()
+    fn transform(
+        &self,
+        input: &[Complex],
+        stride: usize,
+        out: &mut [Complex],
+        factors: &[usize],
+        scratch: &mut [Complex],
+    ) {
+        let Some((&p, factors)) = factors.split_first() else {
+            out[0] = input[0];
+            return;
+        };
+        let n = out.len();
+        let q = n / p;
+        for (r, block) in out.chunks_mut(q).enumerate() {
+            self.transform(&input[r * stride..], stride * p, block, factors, scratch);
+        }
+        // out[k' + s q] = sum over r of e^(2 pi i r (k' + s q) / n) * block r at k'
+        let step = self.len() / n;
+        for k in 0..q {
+            for (r, value) in scratch[..p].iter_mut().enumerate() {
+                *value = out[r * q + k];
+            }
+            for s in 0..p {
+                let kk = k + s * q;
+                out[kk] = scratch[..p]
+                    .iter()
+                    .enumerate()
+                    .fold(Complex::default(), |acc, (r, &value)| {
+                        acc + value * self.twiddles[(r * kk * step) % self.len()]
+                    });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_direct_sum() {
+        for n in [1, 2, 7, 12, 15, 64, 360, 1000] {
+            let input = (0..n)
+                .map(|j| Complex::new((j as f64 * 0.37).sin(), (j as f64 * 1.3).cos()))
+                .collect::<Vec<_>>();
+            let mut out = vec![Complex::default(); n];
+            let fft = Fft::new(n);
+            let mut scratch = vec![Complex::default(); fft.scratch_len()];
+            fft.inverse(&input, &mut out, &mut scratch);
+            for (k, value) in out.iter().enumerate() {
+                let direct = input
+                    .iter()
+                    .enumerate()
+                    .fold(Complex::default(), |acc, (j, &x)| {
+                        acc + x * Complex::cis(2. * PI * (j * k % n) as f64 / n as f64)
+                    });
+                assert!((value.re - direct.re).abs() < 1e-9, "{} {}", n, k);
+                assert!((value.im - direct.im).abs() < 1e-9, "{} {}", n, k);
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! Regular grids of positions for batch evaluation.
+use std::f64::consts::PI;
+
+/// Evenly spaced values along one axis of a [`Grid`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridAxis {
+    /// First value
+    pub start: f64,
+    /// Distance between values
+    pub step: f64,
+    /// Number of values
+    pub count: usize,
+}
+
+impl GridAxis {
+    /// A single value
+    pub fn fixed(value: f64) -> GridAxis {
+        GridAxis {
+            start: value,
+            step: 0.,
+            count: 1,
+        }
+    }
+
+    /// Values from `start` to `end` inclusive, `end` is included when it falls on a step.
+    /// Empty when `end` is before `start` or `step` is not a positive finite number, like
+    /// [`table::yearly`](crate::table::yearly).
+    pub fn range(start: f64, end: f64, step: f64) -> GridAxis {
+        let valid = start.is_finite() && end.is_finite() && step.is_finite() && step > 0.;
+        let count = if valid && end >= start {
+            // allow for rounding in (end - start) / step, e.g. 0.25 degree steps
+            (((end - start) / step + 1e-9).floor() as usize).saturating_add(1)
+        } else {
+            0
+        };
+        GridAxis { start, step, count }
+    }
+
+    /// The `i`th value
+    pub fn value(&self, i: usize) -> f64 {
+        self.start + i as f64 * self.step
+    }
+
+    /// All values in order
+    pub fn values(&self) -> impl Iterator<Item = f64> + '_ {
+        (0..self.count).map(|i| self.value(i))
+    }
+}
+
+/// Latitudes, longitudes and altitudes (or radii for geocentric input) of a grid.
+///
+/// Results are stored with longitude varying fastest, then latitude, then altitude, so the
+/// result for `(i_alt, i_lat, i_lon)` is at [`Grid::index`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+    /// Latitudes (degrees)
+    pub lat: GridAxis,
+    /// Longitudes (degrees)
+    pub lon: GridAxis,
+    /// Altitudes or radii (km)
+    pub alt: GridAxis,
+}
+
+impl Grid {
+    /// Number of positions in the grid, `usize::MAX` if that does not fit
+    pub fn len(&self) -> usize {
+        self.lat
+            .count
+            .checked_mul(self.lon.count)
+            .and_then(|n| n.checked_mul(self.alt.count))
+            .unwrap_or(usize::MAX)
+    }
+
+    /// True if one of the axes has no values
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Position of the result for the given axis indices
+    pub fn index(&self, i_alt: usize, i_lat: usize, i_lon: usize) -> usize {
+        (i_alt * self.lat.count + i_lat) * self.lon.count + i_lon
+    }
+
+    /// Latitude, longitude and altitude of every position, in result order
+    pub fn positions(&self) -> impl Iterator<Item = (f64, f64, f64)> + '_ {
+        self.alt.values().flat_map(move |alt| {
+            self.lat
+                .values()
+                .flat_map(move |lat| self.lon.values().map(move |lon| (lat, lon, alt)))
+        })
+    }
+}
+
+/// How the latitudes of a [`GlobalGrid`] are placed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LatitudeSpacing {
+    /// Evenly spaced from the south to the north pole, both included
+    Equiangular,
+    /// Gauss–Legendre nodes, the zeros of the Legendre polynomial of the same degree as
+    /// the number of latitudes, for integrating over the sphere
+    GaussLegendre,
+}
+
+/// Latitudes and longitudes covering the whole globe for [`ModelSnapshot::try_calc_global`](crate::ModelSnapshot::try_calc_global).
+///
+/// Longitudes are evenly spaced all the way around, so every row can be synthesised with
+/// an FFT. Results are stored with longitude varying fastest, see [`GlobalGrid::index`].
+///
+/// The FFT needs the longitudes converted to radians exactly, while
+/// [`ModelSnapshot::try_calc`](crate::ModelSnapshot::try_calc) and
+/// [`MagneticCircle`](crate::MagneticCircle) use the truncated factor 0.01745329 of
+/// geomag70. The longitudes therefore differ by up to 2.6e-5 degrees at ±180, which
+/// changes the field at the surface by less than 0.01 nT.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlobalGrid {
+    spacing: LatitudeSpacing,
+    lats: Vec<f64>,
+    weights: Vec<f64>,
+    nlon: usize,
+    start_lon: f64,
+}
+
+impl GlobalGrid {
+    /// `nlat` latitudes placed by `spacing`, in increasing order, and `nlon` longitudes
+    /// from 0 degrees east
+    pub fn new(spacing: LatitudeSpacing, nlat: usize, nlon: usize) -> GlobalGrid {
+        let (lats, weights) = match spacing {
+            LatitudeSpacing::Equiangular => equiangular(nlat),
+            LatitudeSpacing::GaussLegendre => gauss_legendre(nlat),
+        };
+        GlobalGrid {
+            spacing,
+            lats,
+            weights,
+            nlon,
+            start_lon: 0.,
+        }
+    }
+
+    /// Starts the longitudes at `lon` (degrees) instead of 0, e.g. -180
+    pub fn with_start_lon(mut self, lon: f64) -> GlobalGrid {
+        self.start_lon = lon;
+        self
+    }
+
+    /// How the latitudes are placed
+    pub fn spacing(&self) -> LatitudeSpacing {
+        self.spacing
+    }
+
+    /// Latitudes (degrees) in increasing order
+    pub fn lats(&self) -> &[f64] {
+        &self.lats
+    }
+
+    /// Quadrature weights of the latitudes, summing to 2. Exact for polynomials in the sine
+    /// of latitude below twice the number of latitudes on a Gauss–Legendre grid,
+    /// trapezoidal in latitude times its cosine on an equiangular grid.
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    /// Number of longitudes
+    pub fn nlon(&self) -> usize {
+        self.nlon
+    }
+
+    /// First longitude (degrees)
+    pub fn start_lon(&self) -> f64 {
+        self.start_lon
+    }
+
+    /// The `i`th longitude (degrees)
+    pub fn lon(&self, i: usize) -> f64 {
+        self.start_lon + 360. * i as f64 / self.nlon as f64
+    }
+
+    /// Number of positions in the grid
+    pub fn len(&self) -> usize {
+        self.lats.len() * self.nlon
+    }
+
+    /// True if there are no latitudes or no longitudes
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Position of the result for the given latitude and longitude indices
+    pub fn index(&self, i_lat: usize, i_lon: usize) -> usize {
+        i_lat * self.nlon + i_lon
+    }
+
+    /// Latitude and longitude of every position, in result order
+    pub fn positions(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.lats
+            .iter()
+            .flat_map(move |&lat| (0..self.nlon).map(move |i| (lat, self.lon(i))))
+    }
+}
+
+fn equiangular(nlat: usize) -> (Vec<f64>, Vec<f64>) {
+    if nlat < 2 {
+        return (vec![0.; nlat], vec![2.; nlat]);
+    }
+    let step = 180. / (nlat - 1) as f64;
+    let lats = (0..nlat)
+        .map(|i| -90. + step * i as f64)
+        .collect::<Vec<_>>();
+    let weights = lats
+        .iter()
+        .enumerate()
+        .map(|(i, lat)| {
+            let end = if i == 0 || i == nlat - 1 { 0.5 } else { 1. };
+            end * step.to_radians() * lat.to_radians().cos()
+        })
+        .collect::<Vec<_>>();
+    // the cosine weights fall a little short of 2, scale them to integrate a constant exactly
+    let total = weights.iter().sum::<f64>();
+    let weights = weights.iter().map(|w| w * 2. / total).collect();
+    (lats, weights)
+}
+
+// Zeros of the Legendre polynomial of degree `nlat` by Newton's method, as latitudes
+fn gauss_legendre(nlat: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut lats = vec![0.; nlat];
+    let mut weights = vec![0.; nlat];
+    let n = nlat as f64;
+    for i in 0..nlat.div_ceil(2) {
+        let mut x = (PI * (i as f64 + 0.75) / (n + 0.5)).cos();
+        let mut dp = 1.;
+        for _ in 0..100 {
+            // P(n, x) and its derivative from the three term recursion
+            let (mut p0, mut p1) = (1., x);
+            for k in 2..=nlat {
+                let k = k as f64;
+                (p0, p1) = (p1, ((2. * k - 1.) * x * p1 - (k - 1.) * p0) / k);
+            }
+            dp = n * (x * p1 - p0) / (x * x - 1.);
+            let dx = p1 / dp;
+            x -= dx;
+            if dx.abs() < 1e-15 {
+                break;
+            }
+        }
+        let lat = x.asin().to_degrees();
+        let weight = 2. / ((1. - x * x) * dp * dp);
+        (lats[nlat - 1 - i], weights[nlat - 1 - i]) = (lat, weight);
+        (lats[i], weights[i]) = (-lat, weight);
+    }
+    (lats, weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_includes_end() {
+        assert_eq!(GridAxis::range(-90., 90., 0.25).count, 721);
+        assert_eq!(GridAxis::range(0., 10., 3.).count, 4);
+        for step in [0., -1., f64::NAN, f64::INFINITY] {
+            assert_eq!(GridAxis::range(0., 10., step).count, 0);
+        }
+        assert_eq!(GridAxis::range(10., 0., 1.).count, 0);
+        assert_eq!(GridAxis::range(0., 1e300, 1e-300).count, usize::MAX);
+        assert_eq!(GridAxis::fixed(5.).values().collect::<Vec<_>>(), [5.]);
+    }
+
+    #[test]
+    fn positions_follow_index() {
+        let grid = Grid {
+            lat: GridAxis::range(-10., 10., 10.),
+            lon: GridAxis::range(0., 20., 5.),
+            alt: GridAxis::range(0., 100., 100.),
+        };
+        let positions = grid.positions().collect::<Vec<_>>();
+        assert_eq!(positions.len(), grid.len());
+        assert_eq!(positions[grid.index(1, 2, 3)], (10., 15., 100.));
+
+        let huge = GridAxis::range(0., 1e12, 1.);
+        let grid = Grid {
+            lat: huge,
+            lon: huge,
+            alt: huge,
+        };
+        assert_eq!(grid.len(), usize::MAX);
+    }
+
+    #[test]
+    fn global_grid_latitudes() {
+        let grid = GlobalGrid::new(LatitudeSpacing::Equiangular, 181, 360).with_start_lon(-180.);
+        assert_eq!(grid.lats()[0], -90.);
+        assert_eq!(grid.lats()[180], 90.);
+        assert_eq!(grid.lon(359), 179.);
+        assert_eq!(grid.positions().nth(grid.index(1, 2)), Some((-89., -178.)));
+
+        for nlat in [1, 2, 5, 64] {
+            let grid = GlobalGrid::new(LatitudeSpacing::GaussLegendre, nlat, 1);
+            assert!(grid.lats().windows(2).all(|w| w[0] < w[1]));
+            // exact for sin(lat)^k up to degree 2 nlat - 1
+            for k in 0..2 * nlat {
+                let sum = grid
+                    .lats()
+                    .iter()
+                    .zip(grid.weights())
+                    .map(|(lat, w)| w * lat.to_radians().sin().powi(k as i32))
+                    .sum::<f64>();
+                let exact = if k % 2 == 0 { 2. / (k + 1) as f64 } else { 0. };
+                assert!((sum - exact).abs() < 1e-12, "{} {}", nlat, k);
+            }
+        }
+    }
+}
@@ -1,141 +1,374 @@
-use std::collections::HashMap;
-const IGRFCOEFFS: &str = include_str!("../../coeffs/shc/igrf14coeffs.txt");
+use super::IgrfGeneration;
+use crate::error::{parse_count, MAX_DEGREE};
+use crate::{math, DateStatus, GeomagError, ModelDate, ParseError};
+
+const IGRF11COEFFS: &str = include_str!("../../coeffs/shc/igrf11coeffs.txt");
+const IGRF12COEFFS: &str = include_str!("../../coeffs/shc/igrf12coeffs.txt");
+const IGRF13COEFFS: &str = include_str!("../../coeffs/shc/igrf13coeffs.txt");
+const IGRF14COEFFS: &str = include_str!("../../coeffs/shc/igrf14coeffs.txt");
 const INTERVAL: f64 = 5.;
 struct CoeffDetails {
-    nmax: i16,
+    nmax: usize,
     coeffs: Vec<f64>,
+    // a definitive model (DGRF)
+    definitive: bool,
+    // predicted from the previous epoch with secular variation
+    predicted: bool,
 }
 pub(crate) struct IGRFCoeffs {
-    coeffs: HashMap<i16, CoeffDetails>,
+    // coefficients of each epoch, in increasing order of the epochs
+    coeffs: Vec<(f64, CoeffDetails)>,
 }
 
-pub fn igrf_data() -> IGRFCoeffs {
-    let parts = IGRFCOEFFS
-        .split('\n')
-        .filter(|s| !s.is_empty())
-        .filter(|s| !s.starts_with('#'))
-        .map(|s| s.split_whitespace().collect::<Vec<_>>())
-        .collect::<Vec<_>>();
+pub fn igrf_data(generation: IgrfGeneration) -> IGRFCoeffs {
+    let shc = match generation {
+        IgrfGeneration::Igrf11 => IGRF11COEFFS,
+        IgrfGeneration::Igrf12 => IGRF12COEFFS,
+        IgrfGeneration::Igrf13 => IGRF13COEFFS,
+        IgrfGeneration::Igrf14 => IGRF14COEFFS,
+    };
+    let mut igrf = parse_shc(shc).unwrap();
+    for (epoch, details) in igrf.coeffs.iter_mut() {
+        details.nmax = generation.nmax(*epoch);
+    }
+    igrf
+}
 
-    let mut coeffs = parts[0]
+fn parse_values(chunks: &[&str], expected: usize, line: usize) -> Result<Vec<f64>, ParseError> {
+    if chunks.len() != expected {
+        return Err(ParseError::malformed(
+            line,
+            format!("expected {} values, found {}", expected, chunks.len()),
+        ));
+    }
+    chunks
         .iter()
-        .skip(3) // skip to year columns
-        .zip(parts[1].iter().skip(3))
-        .enumerate()
-        .map(|(i, (_, b))| {
-            //let hdr = format!("{} {}", a, b);
-            let epoch = if b.contains('-') {
-                2000.
-                    + b.split('-')
-                        .last()
-                        .and_then(|f| f.parse::<f32>().ok())
-                        .unwrap()
-            } else {
-                b.parse::<f32>().unwrap_or_default()
-            };
-            let nmax = if epoch < 2000. {
-                10
-            } else if epoch > 2020. {
-                8
-            } else {
-                13
-            };
-
-            (
-                epoch as i16,
-                CoeffDetails {
-                    nmax,
-                    coeffs: parts
-                        .iter()
-                        .skip(2)
-                        .map(|line| line[i + 3].parse::<f64>().unwrap())
-                        .collect::<Vec<_>>(),
-                },
-            )
+        .map(|c| {
+            c.parse::<f64>()
+                .map_err(|_| ParseError::malformed(line, format!("invalid number '{}'", c)))
         })
-        .collect::<HashMap<i16, CoeffDetails>>();
+        .collect()
+}
 
-    //todo: NASTY
-    let bses = coeffs.get(&2025).unwrap().coeffs.clone();
-    let ases = coeffs.get_mut(&2030).unwrap();
+fn parse_epoch(value: f64, line: usize) -> Result<f64, ParseError> {
+    if !value.is_finite() {
+        return Err(ParseError::malformed(
+            line,
+            format!("epoch {} is not a finite year", value),
+        ));
+    }
+    Ok(value)
+}
 
-    ases.coeffs = ases
-        .coeffs
-        .iter()
-        .zip(bses.iter())
-        .map(|(a, b)| b + a * INTERVAL)
-        .collect();
+// Epochs of the columns have to increase strictly
+fn check_epochs(columns: &[Column], line: usize) -> Result<(), ParseError> {
+    match columns.windows(2).find(|w| w[1].epoch <= w[0].epoch) {
+        Some(w) => Err(ParseError::malformed(
+            line,
+            format!("epoch {} does not follow {}", w[1].epoch, w[0].epoch),
+        )),
+        None => Ok(()),
+    }
+}
 
-    IGRFCoeffs { coeffs }
+struct Column {
+    epoch: f64,
+    sv: bool,
+    definitive: bool,
+    coeffs: Vec<f64>,
 }
 
-fn find_date_factor(start_epoch: i16, end_epoch: i16, date: f64) -> f64 {
-    fn secs_in_year(year: i16) -> i32 {
-        let is_leap = year % 400 == 0 || (year % 4 == 0 && year % 100 != 0);
-        if is_leap {
-            366 * 24 * 3600
-        } else {
-            365 * 24 * 3600
+// Column header in the tabular IGRF format, the last column can be secular variation
+// given as "2025-30" (or "SV" in older generations)
+fn parse_column(column: &str, previous: Option<f64>, line: usize) -> Result<Column, ParseError> {
+    let invalid = || ParseError::malformed(line, format!("invalid epoch '{}'", column));
+    let (epoch, sv) = if column == "SV" {
+        (previous.ok_or_else(invalid)? + INTERVAL, true)
+    } else if let Some((start, end)) = column.split_once('-') {
+        let start = parse_epoch(start.parse::<f64>().map_err(|_| invalid())?, line)?;
+        let end = end.parse::<u8>().map_err(|_| invalid())?;
+        let end = start - start.rem_euclid(100.) + end as f64;
+        (if end < start { end + 100. } else { end }, true)
+    } else {
+        let epoch = column.parse::<f64>().map_err(|_| invalid())?;
+        (parse_epoch(epoch, line)?, false)
+    };
+    Ok(Column {
+        epoch,
+        sv,
+        definitive: false,
+        coeffs: vec![],
+    })
+}
+
+// Turns a trailing secular variation column into coefficients at its end epoch
+fn into_coeffs(mut columns: Vec<Column>, nmax: usize) -> IGRFCoeffs {
+    if let [.., start, end] = &mut columns[..] {
+        if end.sv {
+            let years = end.epoch - start.epoch;
+            end.coeffs = start
+                .coeffs
+                .iter()
+                .zip(end.coeffs.iter())
+                .map(|(a, sv)| a + sv * years)
+                .collect();
         }
     }
+    IGRFCoeffs {
+        coeffs: columns
+            .into_iter()
+            .map(|c| {
+                (
+                    c.epoch,
+                    CoeffDetails {
+                        nmax,
+                        coeffs: c.coeffs,
+                        definitive: c.definitive,
+                        predicted: c.sv,
+                    },
+                )
+            })
+            .collect(),
+    }
+}
 
-    let dte2 = end_epoch as f64;
-    if end_epoch <= start_epoch {
-        0.
-    } else if date > dte2 {
-        let dte1 = start_epoch as f64;
-        (date - dte1) / (dte2 - dte1)
-    } else {
-        let loc_interval = end_epoch - start_epoch;
-        let mut total_secs = 0f64;
-        let mut fraction_secs = 0f64;
-        for x in 0..loc_interval {
-            let year = start_epoch + x;
-            let secs_in_year = secs_in_year(year) as f64;
-            if year == date as i16 {
-                let fraction_coeff = date - date.floor();
-                fraction_secs = total_secs + fraction_coeff * secs_in_year;
+fn gh_slot(n: i64, m: i64, h: bool, nmax: usize, line: usize) -> Result<usize, ParseError> {
+    if n < 1 || n as usize > nmax || m.abs() > n || (h && m == 0) {
+        return Err(ParseError::malformed(
+            line,
+            format!("invalid degree/order {}/{}", n, m),
+        ));
+    }
+    Ok(math::gh_index(n as usize, m.unsigned_abs() as usize) + h as usize)
+}
+
+// Spherical harmonic coefficient file, either the tabular IGRF format
+// ("g/h n m 1900.0 ...") or the standard SHC format where the header line gives
+// nmin, nmax, number of epochs and spline order, followed by a line of epochs
+pub(crate) fn parse_shc(shc: &str) -> Result<IGRFCoeffs, ParseError> {
+    let mut lines = shc
+        .lines()
+        .enumerate()
+        .map(|(i, s)| (i + 1, s.split_whitespace().collect::<Vec<_>>()))
+        .filter(|(_, s)| !s.is_empty() && !s[0].starts_with('#'))
+        .peekable();
+
+    let (line, header) = lines
+        .peek()
+        .cloned()
+        .ok_or_else(|| ParseError::malformed(1, "no coefficients found"))?;
+
+    let rows;
+    let mut columns = vec![];
+    if header.iter().all(|c| c.parse::<f64>().is_ok()) {
+        // standard SHC
+        lines.next();
+        let values = header
+            .iter()
+            .map(|c| c.parse::<f64>().unwrap())
+            .collect::<Vec<_>>();
+        if values.len() < 5 {
+            return Err(ParseError::malformed(
+                line,
+                "expected nmin nmax N order step",
+            ));
+        }
+        let nmax = parse_count(values[1], "nmax", 1, MAX_DEGREE, line)?;
+        parse_count(values[0], "nmin", 0, nmax, line)?;
+        let count = parse_count(values[2], "number of epochs", 1, usize::MAX, line)?;
+        if values[3] > 2. {
+            return Err(ParseError::malformed(
+                line,
+                format!(
+                    "spline order {} not supported, only piecewise linear",
+                    values[3]
+                ),
+            ));
+        }
+        let (line, epochs) = lines
+            .next()
+            .ok_or_else(|| ParseError::malformed(line + 1, "missing epochs"))?;
+        for epoch in parse_values(&epochs, count, line)? {
+            columns.push(Column {
+                epoch: parse_epoch(epoch, line)?,
+                sv: false,
+                definitive: false,
+                coeffs: vec![0.; nmax * (nmax + 2)],
+            });
+        }
+        check_epochs(&columns, line)?;
+        rows = lines.collect::<Vec<_>>();
+        for (line, row) in &rows {
+            let n = row[0].parse::<i64>();
+            let m = row.get(1).and_then(|m| m.parse::<i64>().ok());
+            let (n, m) = match (n, m) {
+                (Ok(n), Some(m)) => (n, m),
+                _ => return Err(ParseError::malformed(*line, "expected degree and order")),
+            };
+            let slot = gh_slot(n, m, m < 0, nmax, *line)?;
+            for (column, value) in columns
+                .iter_mut()
+                .zip(parse_values(&row[2..], count, *line)?)
+            {
+                column.coeffs[slot] = value;
             }
-            total_secs += secs_in_year;
         }
-        fraction_secs / total_secs
+        return Ok(into_coeffs(columns, nmax));
+    }
+
+    // tabular IGRF format, skip ahead to the "g/h n m" header, the line before
+    // labels the columns IGRF, DGRF or SV
+    let mut kinds = vec![];
+    let (line, epochs) = lines
+        .find(|(_, s)| {
+            if s[0] == "c/s" {
+                kinds = s.iter().skip(3).map(|k| k.to_string()).collect();
+            }
+            s[0] == "g/h"
+        })
+        .ok_or_else(|| ParseError::malformed(line, "missing 'g/h n m' header"))?;
+    if epochs.len() < 4 {
+        return Err(ParseError::malformed(line, "no epoch columns"));
+    }
+    let mut previous = None;
+    for (i, column) in epochs.iter().skip(3).enumerate() {
+        if columns.last().is_some_and(|c: &Column| c.sv) {
+            return Err(ParseError::malformed(
+                line,
+                "secular variation must be the last column",
+            ));
+        }
+        let mut column = parse_column(column, previous, line)?;
+        column.definitive = kinds.get(i).is_some_and(|k| k == "DGRF");
+        previous = Some(column.epoch);
+        columns.push(column);
+    }
+    check_epochs(&columns, line)?;
+    rows = lines.collect::<Vec<_>>();
+    let mut parsed = vec![];
+    for (line, row) in &rows {
+        let h = match row[0] {
+            "g" => false,
+            "h" => true,
+            other => {
+                return Err(ParseError::malformed(
+                    *line,
+                    format!("expected 'g' or 'h', found '{}'", other),
+                ))
+            }
+        };
+        let (n, m) = match (
+            row.get(1).and_then(|n| n.parse::<i64>().ok()),
+            row.get(2).and_then(|m| m.parse::<i64>().ok()),
+        ) {
+            (Some(n), Some(m)) if m >= 0 => (n, m),
+            _ => return Err(ParseError::malformed(*line, "expected degree and order")),
+        };
+        let values = parse_values(&row[3..], columns.len(), *line)?;
+        parsed.push((*line, n, m, h, values));
+    }
+    let nmax = parsed
+        .iter()
+        .map(|(_, n, ..)| *n)
+        .max()
+        .ok_or_else(|| ParseError::malformed(line, "no coefficients found"))?;
+    let nmax = parse_count(nmax as f64, "nmax", 1, MAX_DEGREE, line)?;
+    for column in columns.iter_mut() {
+        column.coeffs = vec![0.; nmax * (nmax + 2)];
+    }
+    for (line, n, m, h, values) in parsed {
+        let slot = gh_slot(n, m, h, nmax, line)?;
+        for (column, value) in columns.iter_mut().zip(values) {
+            column.coeffs[slot] = value;
+        }
     }
+    Ok(into_coeffs(columns, nmax))
+}
+
+// Fraction of the interval between the epochs at `date` and its rate of change per year,
+// the years are weighted by their length in seconds both within the interval and when
+// extrapolating past its end
+fn find_date_factor(start_epoch: f64, end_epoch: f64, date: f64) -> (f64, f64) {
+    if end_epoch <= start_epoch {
+        return (0., 0.);
+    }
+    let seconds = |year: f64| ModelDate::from_decimal_year(year).unix();
+    let (start, end) = (seconds(start_epoch), seconds(end_epoch));
+    // the end epoch itself is the end of the last year in the interval
+    let year = date.floor().min(end_epoch - 1.);
+    let year_secs = seconds(year + 1.) - seconds(year);
+    (
+        (seconds(date) - start) / (end - start),
+        year_secs / (end - start),
+    )
 }
 
 impl IGRFCoeffs {
-    fn extrapolate_coeffs(&self, start_epoch: i16, end_epoch: i16, date: f64) -> Vec<f64> {
-        let start = self.coeffs.get(&start_epoch).unwrap();
-        let end = self.coeffs.get(&end_epoch).unwrap();
-        if start.nmax <= end.nmax {
-            panic!("nmax1 <= nmax2")
+    fn extrapolate_coeffs(
+        &self,
+        (start, end): (usize, usize),
+        date: f64,
+    ) -> Result<Vec<f64>, GeomagError> {
+        let (start_epoch, start) = &self.coeffs[start];
+        let (end_epoch, end) = &self.coeffs[end];
+        if start.nmax < end.nmax {
+            return Err(GeomagError::NmaxMismatch {
+                start_epoch: *start_epoch,
+                start_nmax: start.nmax,
+                end_epoch: *end_epoch,
+                end_nmax: end.nmax,
+            });
+        }
+        if start_epoch == end_epoch {
+            return Ok(start.coeffs.clone());
         }
         let k = end.nmax * (end.nmax + 2);
         let l = start.nmax * (start.nmax + 2);
-        start
+        let (factor, _) = find_date_factor(*start_epoch, *end_epoch, date);
+        Ok(start
             .coeffs
             .iter()
             .zip(end.coeffs.iter())
             .enumerate()
             .map(|(i, (coeff_start, coeff_end))| {
-                if (i as i16) >= k && (i as i16) < l {
+                if i >= k && i < l {
                     *coeff_start
                 } else {
-                    let sv = (coeff_end - coeff_start) / INTERVAL;
-                    let factor = date - (start_epoch as f64);
-                    coeff_start + factor * sv
+                    coeff_start + factor * (coeff_end - coeff_start)
                 }
             })
-            .collect::<Vec<f64>>()
+            .collect::<Vec<f64>>())
     }
 
-    fn interpolate_coeffs(&self, start_epoch: i16, end_epoch: i16, date: f64) -> (Vec<f64>, i16) {
-        let factor = find_date_factor(start_epoch, end_epoch, date);
-        let start = self.coeffs.get(&start_epoch).unwrap();
-        let end = self.coeffs.get(&end_epoch).unwrap();
+    fn interpolate_coeffs(&self, interval: (usize, usize), date: f64) -> (Vec<f64>, usize) {
+        let (factor, _) = self.date_factor(interval, date);
+        self.blend(interval, factor, false)
+    }
+
+    // Annual change of the interpolated coefficients at `date`
+    fn interpolate_sv(&self, interval: (usize, usize), date: f64) -> Vec<f64> {
+        let (_, rate) = self.date_factor(interval, date);
+        self.blend(interval, rate, true).0
+    }
+
+    fn date_factor(&self, (start, end): (usize, usize), date: f64) -> (f64, f64) {
+        find_date_factor(self.coeffs[start].0, self.coeffs[end].0, date)
+    }
+
+    // Combines the coefficients of two epochs with `factor`, when `derivative` is set
+    // the terms that do not depend on the factor are left out
+    fn blend(
+        &self,
+        (start, end): (usize, usize),
+        factor: f64,
+        derivative: bool,
+    ) -> (Vec<f64>, usize) {
+        let start = &self.coeffs[start].1;
+        let end = &self.coeffs[end].1;
 
         let (nmax, k, l, interp) = match start.nmax.cmp(&end.nmax) {
             //before 2000.0
-            std::cmp::Ordering::Equal => (start.nmax, start.nmax * (start.nmax + 2), -100, true),
+            std::cmp::Ordering::Equal => (start.nmax, start.nmax * (start.nmax + 2), 0, true),
 
             // between 1995.0 and 2000.0
             std::cmp::Ordering::Less => (
@@ -161,52 +394,186 @@ impl IGRFCoeffs {
             .zip(end.coeffs.iter())
             .enumerate()
             .map(|(i, (coeff_start, coeff_end))| {
-                if k <= (i as i16) && (i as i16) < l {
+                let constant = if derivative { 0. } else { *coeff_start };
+                if k <= i && i < l {
                     if interp {
-                        *coeff_start
+                        constant
                     } else {
                         factor * coeff_end
                     }
                 } else {
-                    coeff_start + factor * (coeff_end - coeff_start)
+                    constant + factor * (coeff_end - coeff_start)
                 }
             })
             .collect::<Vec<_>>();
         (values, nmax)
     }
 
-    pub(crate) fn coeffs(&self, date: f64) -> (Vec<f64>, Vec<f64>, i16) {
-        let years = self.coeffs.keys().collect::<Vec<_>>();
-        if !(1900. ..=2030.).contains(&date) {
-            panic!("Date out of range");
+    // Highest degree of any epoch
+    pub(crate) fn nmax(&self) -> usize {
+        self.coeffs.iter().map(|(_, c)| c.nmax).max().unwrap()
+    }
+
+    // First and last epoch of the model
+    pub(crate) fn validity(&self) -> (f64, f64) {
+        (self.coeffs[0].0, self.coeffs[self.coeffs.len() - 1].0)
+    }
+
+    // Epochs of the interval used at `date` within the validity and how the coefficients
+    // are found there
+    pub(crate) fn date_status(&self, date: f64) -> ((f64, f64), DateStatus) {
+        let (start, end) = self.interval(date);
+        let epochs = (self.coeffs[start].0, self.coeffs[end].0);
+        for (epoch, details) in [&self.coeffs[start], &self.coeffs[end]] {
+            if date == *epoch && details.definitive {
+                return ((date, date), DateStatus::Definitive);
+            }
+        }
+        if self.coeffs[end].1.predicted {
+            (epochs, DateStatus::Predicted)
+        } else {
+            (epochs, DateStatus::Interpolated)
         }
+    }
 
-        let end = **years
+    // Indices of the epochs enclosing `date`, a date on an epoch uses the interval
+    // starting there
+    fn interval(&self, date: f64) -> (usize, usize) {
+        if self.coeffs.len() < 2 {
+            return (0, 0);
+        }
+        let i = self
+            .coeffs
             .iter()
-            .filter(|x| (***x as f64) >= date)
-            .min()
-            .unwrap();
+            .rposition(|(epoch, _)| *epoch <= date)
+            .unwrap_or(0)
+            .min(self.coeffs.len() - 2);
+        (i, i + 1)
+    }
 
-        let start = **years
-            .iter()
-            .filter(|x| (***x as f64) <= date)
-            .max()
-            .unwrap();
+    pub(crate) fn coeffs(&self, date: f64) -> Result<(Vec<f64>, Vec<f64>, usize), GeomagError> {
+        let (min, max) = self.validity();
+        if !(min..=max).contains(&date) {
+            return Err(GeomagError::DateOutOfRange { date, min, max });
+        }
+
+        let interval = self.interval(date);
+        let (coeffs_start, nmax) = self.interpolate_coeffs(interval, date);
 
-        let mac_epoch = **years.iter().max().unwrap();
+        if date + 1. < max {
+            let (coeffs_end, _) = self.interpolate_coeffs(interval, date + 1.);
+            Ok((coeffs_start, coeffs_end, nmax))
+        } else {
+            let coeffs_end = self.extrapolate_coeffs(interval, date + 1.)?;
+            Ok((coeffs_start, coeffs_end, nmax))
+        }
+    }
 
-        if date > 2030. {
-            todo!("2030+")
+    // Coefficients at `date` together with their annual change at that instant
+    pub(crate) fn coeffs_and_sv(
+        &self,
+        date: f64,
+    ) -> Result<(Vec<f64>, Vec<f64>, usize), GeomagError> {
+        let (min, max) = self.validity();
+        if !(min..=max).contains(&date) {
+            return Err(GeomagError::DateOutOfRange { date, min, max });
         }
+        let interval = self.interval(date);
+        let (coeffs, nmax) = self.interpolate_coeffs(interval, date);
+        Ok((coeffs, self.interpolate_sv(interval, date), nmax))
+    }
+}
 
-        let (coeffs_start, nmax) = self.interpolate_coeffs(start, end, date);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        if date + 1. < mac_epoch as f64 {
-            let (coeffs_end, nmax) = self.interpolate_coeffs(start, end, date + 1.);
-            (coeffs_start, coeffs_end, nmax)
-        } else {
-            let coeffs_end = self.extrapolate_coeffs(start, end, date + 1.);
-            (coeffs_start, coeffs_end, nmax)
+    const IGRF14_SHC: &str = include_str!("../../coeffs/shc_nosv/IGRF14.shc");
+
+    #[test]
+    fn tabular_and_shc_agree() {
+        let tabular = parse_shc(IGRF14COEFFS).unwrap();
+        let shc = parse_shc(IGRF14_SHC).unwrap();
+        assert_eq!(tabular.validity(), (1900., 2030.));
+        assert_eq!(shc.validity(), (1900., 2030.));
+        for ((epoch, details), (other_epoch, other)) in tabular.coeffs.iter().zip(&shc.coeffs) {
+            assert_eq!(epoch, other_epoch);
+            for (a, b) in details.coeffs.iter().zip(other.coeffs.iter()) {
+                assert!((a - b).abs() < 1e-6, "{}: {} != {}", epoch, a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn older_generations_parse() {
+        for shc in [
+            IGRF11COEFFS,
+            IGRF12COEFFS,
+            IGRF13COEFFS,
+            include_str!("../../coeffs/shc_nosv/IGRF13.shc"),
+        ] {
+            let (min, _) = parse_shc(shc).unwrap().validity();
+            assert_eq!(min, 1900.);
+        }
+    }
+
+    #[test]
+    fn reports_malformed_line() {
+        let broken = IGRF14_SHC.replacen(" 1   1  -2298", " 1   1  -22x8", 1);
+        match parse_shc(&broken) {
+            Err(ParseError::Malformed { line, .. }) => assert_eq!(line, 7),
+            _ => panic!("expected malformed line"),
+        }
+    }
+
+    #[test]
+    fn fractional_epochs_and_high_degrees() {
+        let mut shc = String::from("1 200 2 2 0.5\n2019.5 2020.0\n");
+        shc.push_str("1 0 -30000.0 -29990.0\n");
+        shc.push_str("200 -200 0.5 1.5\n");
+        let coeffs = parse_shc(&shc).unwrap();
+        assert_eq!(coeffs.validity(), (2019.5, 2020.));
+        assert_eq!(coeffs.nmax(), 200);
+        let (gh, _, nmax) = coeffs.coeffs(2019.5).unwrap();
+        assert_eq!(nmax, 200);
+        assert_eq!(gh[0], -30000.);
+        assert_eq!(gh[200 * 202 - 1], 0.5);
+        let (gh, ..) = coeffs.coeffs(2020.).unwrap();
+        assert_eq!(gh[200 * 202 - 1], 1.5);
+    }
+
+    #[test]
+    fn rejects_decreasing_epochs() {
+        for shc in [
+            "1 1 2 2 5\n2020.0 2015.0\n1 0 1.0 2.0\n",
+            "g/h n m 2020.0 2020.0\ng 1 0 1.0 2.0\n",
+        ] {
+            assert!(
+                matches!(parse_shc(shc), Err(ParseError::Malformed { .. })),
+                "{:?}",
+                shc
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        for shc in [
+            "g/h n m\n",
+            "g/h n m 2020.0\n",
+            "g/h n m 2020.0\ng 100000 0 1.0\n",
+            "1 10000000000 1 1 0\n2020\n",
+            "1 -2 1 1 0\n2020\n",
+            "1 2.5 1 1 0\n2020\n",
+            "-1 2 1 1 0\n2020\n",
+            "1 2 0 1 0\n\n",
+            "1 2 1.5 1 0\n2020\n",
+        ] {
+            assert!(
+                matches!(parse_shc(shc), Err(ParseError::Malformed { line: 1, .. })),
+                "{:?}",
+                shc
+            );
         }
     }
 }
@@ -1,29 +1,266 @@
-use crate::{MagneticComponents, OrthogonalStrength};
+use std::io::Read;
+
+use crate::{
+    math, CoordinateSystem, Extrapolation, FieldGradient, FieldResults, Generation, GeomagError,
+    GeomagneticModel, MagneticComponents, ModelDate, ModelSnapshot, OrthogonalStrength, ParseError,
+    Provenance, SphericalStrength,
+};
 
 mod coeffs;
-mod math;
 
-pub struct IGRFresults {
-    pub result: MagneticComponents,
-    // Annual changes
-    pub sv: MagneticComponents,
+// geomag70 refuses altitudes below 1 km under the ellipsoid, the internal field model
+// itself holds anywhere above the Earth's surface
+const ALTITUDE_RANGE: (f64, f64) = (-1., f64::INFINITY);
+
+pub type IGRFresults = FieldResults;
+
+/// Generations of the International Geomagnetic Reference Field
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IgrfGeneration {
+    /// IGRF-11, definitive to 2005, valid 1900.0 - 2015.0
+    Igrf11,
+    /// IGRF-12, definitive to 2010, valid 1900.0 - 2020.0
+    Igrf12,
+    /// IGRF-13, definitive to 2015, valid 1900.0 - 2025.0
+    Igrf13,
+    /// IGRF-14, definitive to 2020, valid 1900.0 - 2030.0
+    Igrf14,
+}
+
+impl IgrfGeneration {
+    // Epoch of the last main field model, followed by five years of secular variation
+    fn last_main_epoch(self) -> f64 {
+        match self {
+            IgrfGeneration::Igrf11 => 2010.,
+            IgrfGeneration::Igrf12 => 2015.,
+            IgrfGeneration::Igrf13 => 2020.,
+            IgrfGeneration::Igrf14 => 2025.,
+        }
+    }
+
+    /// Name of the generation, e.g. `IGRF-14`
+    pub fn name(self) -> &'static str {
+        match self {
+            IgrfGeneration::Igrf11 => "IGRF-11",
+            IgrfGeneration::Igrf12 => "IGRF-12",
+            IgrfGeneration::Igrf13 => "IGRF-13",
+            IgrfGeneration::Igrf14 => "IGRF-14",
+        }
+    }
+
+    /// First and last date (decimal years) the generation is valid for
+    pub fn validity(self) -> (f64, f64) {
+        (1900., self.last_main_epoch() + 5.)
+    }
+
+    /// Degree of the coefficients at `epoch`: 10 before 2000, 13 from 2000 on
+    /// and 8 for the epoch extrapolated with secular variation
+    pub fn nmax(self, epoch: f64) -> usize {
+        if epoch < 2000. {
+            10
+        } else if epoch > self.last_main_epoch() {
+            8
+        } else {
+            13
+        }
+    }
+}
+
+/// How the annual change (`sv`) of the results is calculated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecularVariation {
+    /// Rate of change at the requested date from the time derivative of the coefficients
+    Analytic,
+    /// Difference between the field at the date and one year later, as geomag70 does
+    FiniteDifference,
 }
 
 pub struct IGRF {
+    name: &'static str,
     coeffs: coeffs::IGRFCoeffs,
+    generation: Generation,
+    sv_mode: SecularVariation,
+    extrapolation: Extrapolation,
 }
 impl Default for IGRF {
     fn default() -> IGRF {
+        IGRF::generation(IgrfGeneration::Igrf14)
+    }
+}
+impl IGRF {
+    /// Creates a model from one of the embedded IGRF generations
+    pub fn generation(generation: IgrfGeneration) -> IGRF {
         IGRF {
-            coeffs: coeffs::igrf_data(),
+            name: generation.name(),
+            coeffs: coeffs::igrf_data(generation),
+            generation: Generation::Igrf(generation),
+            sv_mode: SecularVariation::Analytic,
+            extrapolation: Extrapolation::Error,
         }
     }
+
+    /// Creates a model from spherical harmonic coefficients, either in the tabular
+    /// IGRF format (`igrf14coeffs.txt`) or the standard SHC format (`IGRF14.shc`)
+    pub fn from_shc_str(shc: &str) -> Result<IGRF, ParseError> {
+        Ok(IGRF {
+            name: "SHC",
+            coeffs: coeffs::parse_shc(shc)?,
+            generation: Generation::Custom,
+            sv_mode: SecularVariation::Analytic,
+            extrapolation: Extrapolation::Error,
+        })
+    }
+
+    /// Reads spherical harmonic coefficients, see [`IGRF::from_shc_str`]
+    pub fn from_shc_reader<R: Read>(mut reader: R) -> Result<IGRF, ParseError> {
+        let mut shc = String::new();
+        reader.read_to_string(&mut shc)?;
+        IGRF::from_shc_str(&shc)
+    }
+
+    /// Renames the model, e.g. to tell models loaded at runtime apart
+    pub fn with_name(mut self, name: &str) -> IGRF {
+        self.name = crate::provenance::intern(name);
+        self
+    }
+
+    /// Selects how the annual change is calculated, [`SecularVariation::Analytic`] by default
+    pub fn with_sv_mode(mut self, sv_mode: SecularVariation) -> IGRF {
+        self.sv_mode = sv_mode;
+        self
+    }
+
+    /// Selects what happens to dates outside the validity, [`Extrapolation::Error`] by default
+    pub fn with_extrapolation(mut self, extrapolation: Extrapolation) -> IGRF {
+        self.extrapolation = extrapolation;
+        self
+    }
+
+    /// First and last date (decimal years) the model is valid for
+    pub fn validity(&self) -> (f64, f64) {
+        self.coeffs.validity()
+    }
+
+    /// Lowest and highest altitude (km) the model can be evaluated at
+    pub fn altitude_range(&self) -> (f64, f64) {
+        ALTITUDE_RANGE
+    }
+
+    /// Interpolates the coefficients to `date` (decimal years or a [`ModelDate`]) for
+    /// evaluating many positions, panics if the date is outside the model, see [`IGRF::try_at`]
+    pub fn at(&self, date: impl Into<ModelDate>) -> ModelSnapshot {
+        self.try_at(date).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Interpolates the coefficients to `date`, returning an error outside the model validity
+    pub fn try_at(&self, date: impl Into<ModelDate>) -> Result<ModelSnapshot, GeomagError> {
+        let date = date.into().decimal_year();
+        self.extrapolation
+            .snapshot(self.validity(), date, |date| self.snapshot(date))
+    }
+
+    // Snapshot at a date within the validity
+    fn snapshot(&self, date: f64) -> Result<ModelSnapshot, GeomagError> {
+        let (coeffs, other, nmax) = match self.sv_mode {
+            SecularVariation::Analytic => self.coeffs.coeffs_and_sv(date)?,
+            SecularVariation::FiniteDifference => self.coeffs.coeffs(date)?,
+        };
+        let (epochs, date_status) = self.coeffs.date_status(date);
+        let provenance = Provenance::new(self.name, self.generation, epochs, nmax, date_status);
+        Ok(ModelSnapshot::new(
+            date,
+            provenance,
+            self.reference_radius(),
+            self.altitude_range(),
+            coeffs,
+            other,
+            self.sv_mode,
+        ))
+    }
+
+    /// Calculates the field at geodetic `lat`/`lon` (degrees), `alt` km above the WGS84 ellipsoid
+    /// and `date` in decimal years or as a [`ModelDate`], panics if the input is invalid,
+    /// see [`IGRF::try_calc`]
+    pub fn calc(&self, lat: f64, lon: f64, alt: f64, date: impl Into<ModelDate>) -> IGRFresults {
+        self.try_calc(lat, lon, alt, date)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Calculates the field, returning an error for input outside what the model supports
+    pub fn try_calc(
+        &self,
+        lat: f64,
+        lon: f64,
+        alt: f64,
+        date: impl Into<ModelDate>,
+    ) -> Result<IGRFresults, GeomagError> {
+        self.try_calc_in(CoordinateSystem::Geodetic, lat, lon, alt, date)
+    }
+
+    /// Calculates the field like [`IGRF::try_calc`] with the position given in `coords`,
+    /// `height` is altitude (km) for geodetic and radius (km) for geocentric input
+    pub fn try_calc_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: impl Into<ModelDate>,
+    ) -> Result<IGRFresults, GeomagError> {
+        self.try_at(date)?.try_calc_in(coords, lat, lon, height)
+    }
+
+    /// Spatial derivatives of the main field at the position given in `coords`,
+    /// see [`FieldGradient`]
+    pub fn try_gradient_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: impl Into<ModelDate>,
+    ) -> Result<FieldGradient, GeomagError> {
+        self.try_at(date)?.try_gradient_in(coords, lat, lon, height)
+    }
 }
-impl IGRF {
-    pub fn calc(&self, lat: f64, lon: f64, alt: f64, date: f64) -> IGRFresults {
-        //input validation
-        let (start_coeffs, end_coeffs, nmax) = self.coeffs.coeffs(date);
-        let (a, b) = math::shval3(lat, lon, alt, nmax as usize, &start_coeffs, &end_coeffs);
+
+impl IGRFresults {
+    // Builds the results from the field (a) and its rate of change or the field a year
+    // later (b), depending on `sv_mode`
+    pub(crate) fn new(
+        sv_mode: SecularVariation,
+        provenance: Provenance,
+        a: OrthogonalStrength,
+        b: OrthogonalStrength,
+    ) -> IGRFresults {
+        let results = match sv_mode {
+            SecularVariation::Analytic => IGRFresults::from_rate(a, b),
+            SecularVariation::FiniteDifference => IGRFresults::from_fields(a, b),
+        };
+        IGRFresults {
+            provenance,
+            ..results
+        }
+    }
+
+    // Builds the results from the field and its rate of change per year
+    pub(crate) fn from_rate(field: OrthogonalStrength, rate: OrthogonalStrength) -> IGRFresults {
+        let dif = math::Difh::from_orthognal_strength(&field);
+        IGRFresults {
+            sv: math::secular_variation(&field, &rate),
+            result: MagneticComponents {
+                declination: dif.declination.to_degrees(),
+                inclination: dif.inclination.to_degrees(),
+                horizontal_intensity: dif.horizontal_intensity,
+                orthogonal_strength: field,
+                total_intensity: dif.total_intensity,
+            },
+            provenance: Provenance::default(),
+        }
+    }
+
+    // Builds the results from the field at the requested date (a) and one year later (b)
+    pub(crate) fn from_fields(a: OrthogonalStrength, b: OrthogonalStrength) -> IGRFresults {
         let dif_a = math::Difh::from_orthognal_strength(&a);
         let dif_b = math::Difh::from_orthognal_strength(&b);
 
@@ -52,10 +289,63 @@ impl IGRF {
                 north: b.north - result.orthogonal_strength.north,
                 east: b.east - result.orthogonal_strength.east,
                 down: b.down - result.orthogonal_strength.down,
+                spherical: SphericalStrength {
+                    radial: b.spherical.radial - result.orthogonal_strength.spherical.radial,
+                    theta: b.spherical.theta - result.orthogonal_strength.spherical.theta,
+                    phi: b.spherical.phi - result.orthogonal_strength.spherical.phi,
+                },
             },
             total_intensity: dif_b.total_intensity - dif_a.total_intensity,
         };
 
-        IGRFresults { result, sv }
+        IGRFresults {
+            result,
+            sv,
+            provenance: Provenance::default(),
+        }
+    }
+}
+
+impl GeomagneticModel for IGRF {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn validity(&self) -> (f64, f64) {
+        IGRF::validity(self)
+    }
+
+    fn altitude_range(&self) -> (f64, f64) {
+        IGRF::altitude_range(self)
+    }
+
+    fn nmax(&self) -> usize {
+        self.coeffs.nmax()
+    }
+
+    fn try_at(&self, date: f64) -> Result<ModelSnapshot, GeomagError> {
+        IGRF::try_at(self, date)
+    }
+
+    fn try_calc_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: f64,
+    ) -> Result<FieldResults, GeomagError> {
+        IGRF::try_calc_in(self, coords, lat, lon, height, date)
+    }
+
+    fn try_gradient_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: f64,
+    ) -> Result<FieldGradient, GeomagError> {
+        IGRF::try_gradient_in(self, coords, lat, lon, height, date)
     }
 }
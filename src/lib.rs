@@ -1,5 +1,60 @@
+pub mod circle;
+pub mod cof;
+pub mod date;
+pub mod dipole;
+mod error;
+mod fft;
+pub mod grid;
 pub mod igrf;
+mod math;
+pub mod mlt;
+pub mod model;
+pub mod poles;
+pub mod provenance;
+mod sh;
+pub mod snapshot;
+pub mod table;
+pub mod trace;
+pub mod wmm;
+pub mod zones;
 
+pub use circle::MagneticCircle;
+pub use date::ModelDate;
+pub use dipole::{Dipole, EccentricDipole};
+pub use error::{GeomagError, ParseError};
+pub use grid::{GlobalGrid, Grid, GridAxis, LatitudeSpacing};
+pub use mlt::MltFrame;
+pub use model::{Extrapolation, GeomagneticModel, Registry};
+pub use poles::{DipPoles, PoleTrack};
+pub use provenance::{DateStatus, Generation, Provenance};
+pub use snapshot::ModelSnapshot;
+pub use table::ReferenceTable;
+pub use trace::{FieldLine, TraceDirection, TraceOptions, TraceStop};
+pub use zones::{CompassZone, ZoneBoundaries};
+
+/// How positions passed to the models are interpreted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoordinateSystem {
+    /// Geodetic latitude, height (km) above the WGS84 ellipsoid,
+    /// X/Y/Z are given in the geodetic (local horizon) frame
+    Geodetic,
+    /// Geocentric latitude, distance (km) from the centre of the Earth,
+    /// X/Y/Z are given in the geocentric frame
+    Geocentric,
+}
+
+/// Field components in spherical coordinates at the geocentric position
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SphericalStrength {
+    /// Radial component, positive outwards (Br) (nT)
+    pub radial: f64,
+    /// Colatitude component, positive southwards (Btheta) (nT)
+    pub theta: f64,
+    /// Longitude component, positive eastwards (Bphi) (nT)
+    pub phi: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct OrthogonalStrength {
     /// North component (X) (nT)
     pub north: f64,
@@ -7,6 +62,8 @@ pub struct OrthogonalStrength {
     pub east: f64,
     /// Down / Vertical component (Z) (nT)
     pub down: f64,
+    /// Spherical components, unaffected by the coordinate system
+    pub spherical: SphericalStrength,
 }
 
 impl Default for OrthogonalStrength {
@@ -15,12 +72,14 @@ impl Default for OrthogonalStrength {
             north: 0.0,
             east: 0.0,
             down: 0.0,
+            spherical: SphericalStrength::default(),
         }
     }
 }
 
 /// Components needed to determine Earth's magnetic field at a given location
 /// Detailed info: https://www.geomag.nrcan.gc.ca/mag_fld/comp-en.php
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct MagneticComponents {
     /// Declination (D) (degrees)
     pub declination: f64,
@@ -32,4 +91,40 @@ pub struct MagneticComponents {
     pub orthogonal_strength: OrthogonalStrength,
     /// Total intensity (F) (nT)
     pub total_intensity: f64,
-}
\ No newline at end of file
+}
+
+/// Spatial derivatives of the X/Y/Z components in the frame of the input coordinates
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FieldGradient {
+    /// Gradient tensor (nT/km), `tensor[i][j]` is the derivative of
+    /// component i of (X, Y, Z) along direction j of (north, east, down)
+    pub tensor: [[f64; 3]; 3],
+    /// Derivative of (X, Y, Z) with respect to latitude (nT/degree)
+    pub d_lat: [f64; 3],
+    /// Derivative of (X, Y, Z) with respect to longitude (nT/degree)
+    pub d_lon: [f64; 3],
+    /// Derivative of (X, Y, Z) with respect to altitude, or radius for geocentric input (nT/km)
+    pub d_alt: [f64; 3],
+}
+
+/// The field at a location together with its annual change
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FieldResults {
+    pub result: MagneticComponents,
+    // Annual changes
+    pub sv: MagneticComponents,
+    /// Model and coefficients the field was calculated with
+    pub provenance: Provenance,
+}
+
+impl FieldResults {
+    /// Whether the date is outside the validity of the model, see [`Extrapolation`]
+    pub fn extrapolated(&self) -> bool {
+        self.provenance.date_status == DateStatus::Extrapolated
+    }
+
+    /// Whether a compass is reliable here, from the horizontal intensity
+    pub fn compass_zone(&self) -> CompassZone {
+        CompassZone::from_horizontal_intensity(self.result.horizontal_intensity)
+    }
+}
@@ -0,0 +1,807 @@
+use crate::sh;
+use crate::{
+    CoordinateSystem, FieldGradient, MagneticComponents, OrthogonalStrength, SphericalStrength,
+};
+
+// Reference radius (km) of the spherical harmonic expansions, mean radius of the Earth
+pub const EARTHS_RADIUS: f64 = 6371.2;
+// WGS84 semi-major and semi-minor axes (km)
+const WGS84_A: f64 = 6378.137;
+const WGS84_B: f64 = 6356.7523142;
+
+// Height (km) above the WGS84 ellipsoid of a point at geocentric `lat` (degrees) and radius `r` (km),
+// measured along the radius which is close enough for range checks
+pub fn geocentric_altitude(lat: f64, r: f64) -> f64 {
+    let (s, c) = lat.to_radians().sin_cos();
+    let a2 = WGS84_A * WGS84_A;
+    let b2 = WGS84_B * WGS84_B;
+    r - (a2 * b2 / (b2 * c * c + a2 * s * s)).sqrt()
+}
+
+// Geocentric cartesian position (km) of geodetic `lat`/`lon` (degrees) and `alt` km above
+// the WGS84 ellipsoid
+pub fn geodetic_to_cartesian(lat: f64, lon: f64, alt: f64) -> [f64; 3] {
+    let (slat, clat) = lat.to_radians().sin_cos();
+    let (slon, clon) = lon.to_radians().sin_cos();
+    let e2 = 1. - (WGS84_B * WGS84_B) / (WGS84_A * WGS84_A);
+    let n = WGS84_A / (1. - e2 * slat * slat).sqrt();
+    [
+        (n + alt) * clat * clon,
+        (n + alt) * clat * slon,
+        (n * (1. - e2) + alt) * slat,
+    ]
+}
+
+// Geodetic latitude, longitude (degrees) and altitude (km) of the geocentric cartesian
+// position `p` (km), iterating on the latitude which settles in a few steps
+pub fn cartesian_to_geodetic(p: [f64; 3]) -> (f64, f64, f64) {
+    let e2 = 1. - (WGS84_B * WGS84_B) / (WGS84_A * WGS84_A);
+    let rho = p[0].hypot(p[1]);
+    let mut lat = p[2].atan2(rho * (1. - e2));
+    let mut alt = 0.;
+    for _ in 0..10 {
+        let (s, c) = lat.sin_cos();
+        let n = WGS84_A / (1. - e2 * s * s).sqrt();
+        alt = rho * c + p[2] * s - WGS84_A * WGS84_A / n;
+        let next = p[2].atan2(rho * (1. - e2 * n / (n + alt)));
+        if (next - lat).abs() < 1e-15 {
+            break;
+        }
+        lat = next;
+    }
+    (lat.to_degrees(), p[1].atan2(p[0]).to_degrees(), alt)
+}
+
+// Unit vector towards geocentric `lat`/`lon` (degrees), z along the rotation axis and
+// x through longitude 0
+pub fn cartesian(lat: f64, lon: f64) -> [f64; 3] {
+    let (slat, clat) = lat.to_radians().sin_cos();
+    let (slon, clon) = lon.to_radians().sin_cos();
+    [clat * clon, clat * slon, slat]
+}
+
+// Latitude and longitude (degrees) of the direction `v`
+pub fn spherical(v: [f64; 3]) -> (f64, f64) {
+    let lat = v[2].atan2(v[0].hypot(v[1])).to_degrees();
+    (lat, v[1].atan2(v[0]).to_degrees())
+}
+
+pub fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn norm(v: [f64; 3]) -> f64 {
+    dot(v, v).sqrt()
+}
+
+pub struct Difh {
+    pub declination: f64,
+    pub inclination: f64,
+    pub horizontal_intensity: f64,
+    pub total_intensity: f64,
+}
+
+impl Difh {
+    pub fn from_orthognal_strength(p: &OrthogonalStrength) -> Self {
+        const SN: f64 = 0.0001;
+
+        let horizontal_intensity = (p.north * p.north + p.east * p.east).sqrt();
+        let total_intensity = (p.north * p.north + p.east * p.east + p.down * p.down).sqrt();
+
+        let inclination = if total_intensity < SN {
+            f64::NAN
+        } else {
+            p.down.atan2(horizontal_intensity)
+        };
+
+        let declination = if total_intensity < SN || horizontal_intensity < SN {
+            f64::NAN
+        } else if horizontal_intensity + p.north < SN {
+            std::f64::consts::PI
+        } else {
+            2.0 * p.east.atan2(horizontal_intensity + p.north)
+        };
+        Self {
+            declination,
+            inclination,
+            horizontal_intensity,
+            total_intensity,
+        }
+    }
+}
+
+// Position of g(n, m) in the geomag70 coefficient ordering (g10, g11, h11, g20, g21, h21, ...),
+// h(n, m) follows directly after for m > 0
+pub fn gh_index(n: usize, m: usize) -> usize {
+    n * n - 1 + if m == 0 { 0 } else { 2 * m - 1 }
+}
+
+// Annual change of all components from the field and the rate of change of X, Y and Z.
+// D and I are returned in minutes per year, the rest in nT per year.
+pub fn secular_variation(b: &OrthogonalStrength, db: &OrthogonalStrength) -> MagneticComponents {
+    let h2 = b.north * b.north + b.east * b.east;
+    let h = h2.sqrt();
+    let f2 = h2 + b.down * b.down;
+    let f = f2.sqrt();
+    let hdot = (b.north * db.north + b.east * db.east) / h;
+    MagneticComponents {
+        declination: ((b.north * db.east - b.east * db.north) / h2).to_degrees() * 60.,
+        inclination: ((h * db.down - b.down * hdot) / f2).to_degrees() * 60.,
+        horizontal_intensity: hdot,
+        orthogonal_strength: OrthogonalStrength {
+            north: db.north,
+            east: db.east,
+            down: db.down,
+            spherical: db.spherical,
+        },
+        total_intensity: (b.north * db.north + b.east * db.east + b.down * db.down) / f,
+    }
+}
+
+//https://github.com/igp-gravity/geoist/blob/658aadab8074bffcbc6b3861671d35b3012502e9/geoist/pfm/igrf.py
+//https://github.com/SuperDARN/rst/tree/734542037d86b24475d8da571ed668659a858e27/codebase/analysis/src.lib/igrf/igrf.1.13
+//https://github.com/wallscavesurvey/walls/blob/master/geomag70/geomag70_org.c
+//https://github.com/proway2/go-igrf
+
+// Degrees to radians, truncated as in geomag70
+const DTR: f64 = 0.01745329;
+
+// Longitude (degrees) in radians as used by `Position`
+pub fn longitude(flon: f64) -> f64 {
+    flon * DTR
+}
+
+// Whether `Position` moves latitude `flat` (degrees) off the pole
+pub fn near_pole(flat: f64) -> bool {
+    (90.0 - flat.abs()) < 0.001
+}
+
+// Geocentric position of a point together with the rotation from the geocentric frame
+// back to the frame the point was given in
+pub struct Position {
+    // sine and cosine of the geocentric latitude
+    pub slat: f64,
+    pub clat: f64,
+    // longitude (radians)
+    pub lon: f64,
+    // distance from the centre of the Earth (km)
+    pub r: f64,
+    cd: f64,
+    sd: f64,
+}
+
+impl Position {
+    // Converts latitude/longitude (degrees) and elevation (km) as geomag70 does,
+    // including nudging the poles to 89.999 degrees
+    pub fn new(flat: f64, flon: f64, elev: f64, coords: CoordinateSystem) -> Position {
+        // a2,b2     - squares of semi-major and semi-minor axes of
+        // the reference spheroid used for transforming
+        // between geodetic and geocentric coordinates or components
+        let a2: f64 = 40680631.59; /* WGS84 */
+        let b2: f64 = 40408299.98; /* WGS84 */
+
+        let mut slat: f64 = (flat * DTR).sin();
+
+        let mut clat = {
+            let aa = if near_pole(flat) {
+                89.999f64.copysign(flat)
+            } else {
+                flat
+            };
+            (aa * DTR).cos()
+        };
+
+        // geocentric input is already a radius, nothing to rotate
+        let (r, cd, sd) = match coords {
+            CoordinateSystem::Geocentric => (elev, 1.0, 0.0),
+            CoordinateSystem::Geodetic => {
+                // this block is for geodetic coordinate system ->
+                let aa = a2 * clat * clat;
+                let bb = b2 * slat * slat;
+                let cc = aa + bb;
+                let dd = cc.sqrt();
+                let r = (elev * (elev + 2.0 * dd) + (a2 * aa + b2 * bb) / cc).sqrt();
+                let cd = (elev + dd) / r;
+                let sd = (a2 - b2) / dd * slat * clat / r;
+                {
+                    let old_slat = slat;
+                    slat = slat * cd - clat * sd;
+                    clat = clat * cd + old_slat * sd;
+                }
+                // <- this block is for geodetic coordinate system
+                (r, cd, sd)
+            }
+        };
+        Position {
+            slat,
+            clat,
+            lon: longitude(flon),
+            r,
+            cd,
+            sd,
+        }
+    }
+
+    // Colatitude, longitude and radius as used by the synthesis of an expansion with
+    // reference radius `radius` (km)
+    pub(crate) fn point(&self, radius: f64) -> sh::Point {
+        sh::Point {
+            t: self.slat,
+            u: self.clat,
+            lon: self.lon,
+            ratio: radius / self.r,
+        }
+    }
+
+    // Turns geocentric north, east and down components into the frame of the input
+    pub fn field(&self, (x, y, z): (f64, f64, f64)) -> OrthogonalStrength {
+        OrthogonalStrength {
+            north: x * self.cd + z * self.sd,
+            east: y,
+            down: z * self.cd - x * self.sd,
+            spherical: SphericalStrength {
+                radial: -z,
+                theta: -x,
+                phi: y,
+            },
+        }
+    }
+}
+
+// // Computes field components from spherical harmonic (sh) models.
+// // The calculation is performed for two sets of coeffs for a single location,
+// // thus it returns two sets of X, Y, Z.
+// //
+// // X - northward component
+// //
+// // Y - eastward component
+// //
+// // Z - vertically-downward component
+pub fn shval3(
+    pos: &Position,
+    radius: f64,
+    nmax: usize,
+    gha: &[f64],
+    ghb: &[f64],
+) -> (OrthogonalStrength, OrthogonalStrength) {
+    let point = pos.point(radius);
+    (
+        pos.field(sh::field(nmax, gha, &point)),
+        pos.field(sh::field(nmax, ghb, &point)),
+    )
+}
+
+// Gradient of the field of a single coefficient set, see `FieldGradient`
+pub fn gradient(
+    flat: f64,
+    flon: f64,
+    elev: f64,
+    coords: CoordinateSystem,
+    radius: f64,
+    nmax: usize,
+    gh: &[f64],
+) -> FieldGradient {
+    let pos = Position::new(flat, flon, elev, coords);
+    let (field, t) = sh::gradient(nmax, gh, &pos.point(radius), pos.r);
+
+    // rotate the tensor into the frame of the input, R * T * R^T
+    let (cd, sd) = (pos.cd, pos.sd);
+    let rot = [[cd, 0., sd], [0., 1., 0.], [-sd, 0., cd]];
+    let mut tensor = [[0.; 3]; 3];
+    for (i, row) in tensor.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            for k in 0..3 {
+                for l in 0..3 {
+                    *v += rot[i][k] * t[k][l] * rot[j][l];
+                }
+            }
+        }
+    }
+    let b = pos.field(field);
+    let (x, y, z) = (b.north, b.east, b.down);
+
+    // distance moved per radian of latitude and longitude, the frame turns along with it
+    let (s, c) = flat.to_radians().sin_cos();
+    let (meridian, normal) = match coords {
+        CoordinateSystem::Geocentric => (elev, elev),
+        CoordinateSystem::Geodetic => {
+            let e2 = 1. - (WGS84_B * WGS84_B) / (WGS84_A * WGS84_A);
+            let w = (1. - e2 * s * s).sqrt();
+            (WGS84_A * (1. - e2) / (w * w * w) + elev, WGS84_A / w + elev)
+        }
+    };
+    let per_degree = 1f64.to_radians();
+    let col = |j: usize| [tensor[0][j], tensor[1][j], tensor[2][j]];
+    let (north, east, down) = (col(0), col(1), col(2));
+    FieldGradient {
+        tensor,
+        d_lat: [
+            (meridian * north[0] + z) * per_degree,
+            meridian * north[1] * per_degree,
+            (meridian * north[2] - x) * per_degree,
+        ],
+        d_lon: [
+            (normal * c * east[0] - s * y) * per_degree,
+            (normal * c * east[1] + s * x + c * z) * per_degree,
+            (normal * c * east[2] - c * y) * per_degree,
+        ],
+        d_alt: [-down[0], -down[1], -down[2]],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use super::*;
+
+    #[test]
+    fn difh_xyz() {
+        let difh = Difh::from_orthognal_strength(&OrthogonalStrength {
+            north: 7074.026894642207,
+            east: 4596.188334222297,
+            down: 62650.49087477549,
+            ..Default::default()
+        });
+        assert_float_eq!(difh.declination, 0.5761834859773884, rel <= 1e-12);
+        assert_float_eq!(difh.inclination, 1.4369489460941476, rel <= 1e-12);
+        assert_float_eq!(difh.horizontal_intensity, 8436.041945709041, rel <= 1e-12);
+        assert_float_eq!(difh.total_intensity, 63215.90630972626, rel <= 1e-12);
+    }
+
+    #[test]
+    fn test_shval3() {
+        let start_coeffs: [f64; 195] = [
+            -31535.104326396497,
+            -2298.,
+            5920.7007119386635,
+            -682.0972070098576,
+            2907.2987404162104,
+            -1063.498630887185,
+            935.6935925520263,
+            1115.4030668127054,
+            1023.499178532311,
+            -1471.498630887185,
+            -332.6985213581599,
+            1254.3009309967142,
+            6.09830230010953,
+            578.2965498357065,
+            518.7023548740416,
+            876.3997809419496,
+            629.499178532311,
+            195.79956188389923,
+            659.3003833515882,
+            -69.79956188389923,
+            -362.89895947426066,
+            -209.10049288061336,
+            135.19934282584884,
+            -74.00054764512596,
+            -184.79956188389923,
+            328.,
+            -208.30093099671413,
+            263.500273822563,
+            53.29983570646221,
+            4.400328587075575,
+            -32.900054764512596,
+            -86.69961664841183,
+            -124.09994523548741,
+            -16.99945235487404,
+            3.7995618838992335,
+            62.900054764512596,
+            60.900054764512596,
+            -8.800109529025193,
+            -11.,
+            83.29983570646222,
+            -217.39978094194961,
+            2.1998904709748084,
+            -57.900054764512596,
+            -34.70016429353779,
+            58.80010952902519,
+            35.600219058050385,
+            -90.19989047097481,
+            -68.80010952902519,
+            70.,
+            -54.900054764512596,
+            -45.099945235487404,
+            0.,
+            -13.099945235487404,
+            33.900054764512596,
+            -10.099945235487404,
+            -41.,
+            -0.9000547645125958,
+            -20.900054764512596,
+            28.,
+            18.,
+            -12.,
+            6.,
+            -22.,
+            11.,
+            8.,
+            8.,
+            -4.,
+            -14.099945235487404,
+            -9.,
+            7.,
+            1.,
+            -13.,
+            2.,
+            5.,
+            -8.900054764512596,
+            16.,
+            5.,
+            -5.,
+            8.,
+            -18.,
+            8.,
+            10.,
+            -20.,
+            1.,
+            14.,
+            -11.,
+            5.,
+            12.,
+            -3.,
+            1.,
+            -2.,
+            -2.,
+            8.,
+            2.,
+            10.,
+            -0.9000547645125958,
+            -2.,
+            -1.,
+            2.,
+            -3.,
+            -4.,
+            2.,
+            2.,
+            1.,
+            -5.,
+            2.,
+            -2.,
+            6.,
+            6.,
+            -4.,
+            4.,
+            0.,
+            0.,
+            -2.,
+            2.,
+            4.,
+            2.,
+            0.,
+            0.,
+            -6.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+        ];
+
+        let end_coeffs: [f64; 195] = [
+            -31519.312979189486,
+            -2298.,
+            5918.1021358159915,
+            -692.2916210295729,
+            2911.896221248631,
+            -1068.4958926615552,
+            959.0807776560789,
+            1104.209200438116,
+            1026.4975355969332,
+            -1476.4958926615552,
+            -338.09556407447974,
+            1250.9027929901424,
+            12.294906900328586,
+            590.8896495071194,
+            510.1070646221249,
+            877.1993428258488,
+            632.4975355969332,
+            197.3986856516977,
+            657.9011500547645,
+            -71.3986856516977,
+            -366.69687842278205,
+            -207.30147864184008,
+            137.59802847754656,
+            -72.00164293537787,
+            -186.3986856516977,
+            328.,
+            -204.9027929901424,
+            262.5008214676889,
+            53.89950711938664,
+            3.2009857612267254,
+            -32.70016429353779,
+            -88.09884994523549,
+            -124.29983570646222,
+            -18.998357064622123,
+            5.3986856516977,
+            62.70016429353779,
+            60.70016429353779,
+            -8.400328587075576,
+            -11.,
+            83.89950711938664,
+            -218.19934282584884,
+            2.599671412924425,
+            -57.70016429353779,
+            -34.10049288061336,
+            58.40032858707558,
+            34.80065717415115,
+            -90.59967141292442,
+            -68.40032858707558,
+            70.,
+            -54.70016429353779,
+            -45.29983570646221,
+            0.,
+            -13.299835706462213,
+            33.70016429353779,
+            -10.299835706462213,
+            -41.,
+            -0.7001642935377875,
+            -20.70016429353779,
+            28.,
+            18.,
+            -12.,
+            6.,
+            -22.,
+            11.,
+            8.,
+            8.,
+            -4.,
+            -14.299835706462213,
+            -9.,
+            7.,
+            1.,
+            -13.,
+            2.,
+            5.,
+            -8.700164293537787,
+            16.,
+            5.,
+            -5.,
+            8.,
+            -18.,
+            8.,
+            10.,
+            -20.,
+            1.,
+            14.,
+            -11.,
+            5.,
+            12.,
+            -3.,
+            1.,
+            -2.,
+            -2.,
+            8.,
+            2.,
+            10.,
+            -0.7001642935377875,
+            -2.,
+            -1.,
+            2.,
+            -3.,
+            -4.,
+            2.,
+            2.,
+            1.,
+            -5.,
+            2.,
+            -2.,
+            6.,
+            6.,
+            -4.,
+            4.,
+            0.,
+            0.,
+            -2.,
+            2.,
+            4.,
+            2.,
+            0.,
+            0.,
+            -6.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+            0.,
+        ];
+        let pos = Position::new(59.9, -109.9, 1.1, CoordinateSystem::Geodetic);
+        let (a, b) = shval3(&pos, EARTHS_RADIUS, 10, &start_coeffs, &end_coeffs);
+        assert_float_eq!(a.north, 7074.026894642207, rel <= 1e-12);
+        assert_float_eq!(a.east, 4596.188334222297, rel <= 1e-12);
+        assert_float_eq!(a.down, 62650.49087477549, rel <= 1e-12);
+        assert_float_eq!(b.north, 7080.6056093223815, rel <= 1e-12);
+        assert_float_eq!(b.east, 4593.347978266618, rel <= 1e-12);
+        assert_float_eq!(b.down, 62630.350967492595, rel <= 1e-12);
+    }
+
+    #[test]
+    fn shval3_beyond_degree_13() {
+        // an axial dipole padded with zero coefficients up to degree 30
+        let nmax = 30;
+        let mut gh = vec![0.; nmax * (nmax + 2)];
+        gh[0] = -30000.;
+        let r = 2. * EARTHS_RADIUS;
+        let pos = Position::new(0., 45., r, CoordinateSystem::Geocentric);
+        let (a, _) = shval3(&pos, EARTHS_RADIUS, nmax, &gh, &gh);
+        assert_float_eq!(a.north, 30000. / 8., rel <= 1e-12);
+        assert_float_eq!(a.east, 0., abs <= 1e-9);
+        assert_float_eq!(a.down, 0., abs <= 1e-9);
+    }
+
+    #[test]
+    fn shval3_reference_radius() {
+        // the same dipole coefficients relative to a reference radius of 3396.2 km (Mars)
+        // give the same field at that radius as at the earth's
+        let gh = [-30000., 1000., 2000.];
+        let pos = Position::new(10., 45., EARTHS_RADIUS, CoordinateSystem::Geocentric);
+        let (earth, _) = shval3(&pos, EARTHS_RADIUS, 1, &gh, &gh);
+        let pos = Position::new(10., 45., 3396.2, CoordinateSystem::Geocentric);
+        let (mars, _) = shval3(&pos, 3396.2, 1, &gh, &gh);
+        assert_float_eq!(earth.north, mars.north, rel <= 1e-12);
+        assert_float_eq!(earth.east, mars.east, rel <= 1e-12);
+        assert_float_eq!(earth.down, mars.down, rel <= 1e-12);
+        let pos = Position::new(10., 45., 2. * 3396.2, CoordinateSystem::Geocentric);
+        let (above, _) = shval3(&pos, 3396.2, 1, &gh, &gh);
+        assert_float_eq!(above.down, earth.down / 8., rel <= 1e-12);
+    }
+
+    #[test]
+    fn geodetic_conversion() {
+        for (lat, lon, alt) in [(0., 0., 0.), (45., -120., 10.), (-89.99, 10., 30000.)] {
+            let p = geodetic_to_cartesian(lat, lon, alt);
+            let (back_lat, back_lon, back_alt) = cartesian_to_geodetic(p);
+            assert!((back_lat - lat).abs() < 1e-9 && (back_lon - lon).abs() < 1e-9);
+            assert!((back_alt - alt).abs() < 1e-6);
+        }
+    }
+}
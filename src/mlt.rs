@@ -0,0 +1,126 @@
+//! Magnetic local time from the position of the sun in the dipole frames.
+use crate::error::check_input;
+use crate::{
+    math, CoordinateSystem, Dipole, EccentricDipole, GeomagError, ModelDate, ModelSnapshot,
+};
+
+// Julian date of J2000.0, 2000-01-01 12:00
+const J2000: f64 = 2451545.;
+
+/// Dipole frame the magnetic local time is measured in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MltFrame {
+    /// Longitudes about the centred dipole, see [`Dipole`]
+    Dipole,
+    /// Longitudes about the eccentric dipole, see [`EccentricDipole`]
+    Eccentric,
+}
+
+/// Geocentric latitude and longitude (degrees) of the point where the sun is overhead at
+/// `instant` (UTC).
+///
+/// Uses the low precision formulas of the Astronomical Almanac, good to about 0.01 degrees
+/// between 1950 and 2050.
+pub fn subsolar_point(instant: impl Into<ModelDate>) -> (f64, f64) {
+    let days = instant.into().julian_date() - J2000;
+    // mean longitude and anomaly, then ecliptic longitude and obliquity
+    let mean_longitude = 280.460 + 0.9856474 * days;
+    let anomaly = (357.528 + 0.9856003 * days).to_radians();
+    let longitude =
+        (mean_longitude + 1.915 * anomaly.sin() + 0.020 * (2. * anomaly).sin()).to_radians();
+    let obliquity = (23.439 - 0.0000004 * days).to_radians();
+    let right_ascension = (obliquity.cos() * longitude.sin()).atan2(longitude.cos());
+    let declination = (obliquity.sin() * longitude.sin()).asin();
+    // Greenwich mean sidereal time (degrees)
+    let sidereal = 280.46061837 + 360.98564736629 * days;
+    let lon = (right_ascension.to_degrees() - sidereal + 180.).rem_euclid(360.) - 180.;
+    (declination.to_degrees(), lon)
+}
+
+// Hours from magnetic midnight of magnetic longitude `mlon` when the sun is at `sun_mlon`
+fn local_time(mlon: f64, sun_mlon: f64) -> f64 {
+    (12. + (mlon - sun_mlon) / 15.).rem_euclid(24.)
+}
+
+impl Dipole {
+    /// Magnetic local time (hours) at geocentric `lat`/`lon` (degrees) at `instant` (UTC),
+    /// from the geomagnetic longitudes of the position and the subsolar point
+    pub fn mlt(&self, lat: f64, lon: f64, instant: impl Into<ModelDate>) -> f64 {
+        let (sun_lat, sun_lon) = subsolar_point(instant);
+        let (_, sun_mlon) = self.to_geomagnetic(sun_lat, sun_lon);
+        local_time(self.to_geomagnetic(lat, lon).1, sun_mlon)
+    }
+}
+
+impl EccentricDipole {
+    /// Magnetic local time (hours) at geocentric `lat`/`lon` (degrees) and `radius` (km) at
+    /// `instant` (UTC), from the eccentric longitude of the position and the direction of the
+    /// sun, which is far enough away for the offset of the centre not to matter
+    pub fn mlt(&self, lat: f64, lon: f64, radius: f64, instant: impl Into<ModelDate>) -> f64 {
+        let (sun_lat, sun_lon) = subsolar_point(instant);
+        let (_, sun_mlon) = self.dipole().to_geomagnetic(sun_lat, sun_lon);
+        local_time(self.to_eccentric(lat, lon, radius).1, sun_mlon)
+    }
+}
+
+impl ModelSnapshot {
+    /// Magnetic local time (hours) in `frame` at the position given in `coords` at `instant`
+    /// (UTC), `height` is altitude (km) for geodetic and radius (km) for geocentric input.
+    /// The dipole is taken at the date of the snapshot.
+    pub fn try_mlt(
+        &self,
+        frame: MltFrame,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        instant: impl Into<ModelDate>,
+    ) -> Result<f64, GeomagError> {
+        let instant = instant.into();
+        if !instant.decimal_year().is_finite() {
+            return Err(GeomagError::NonFinite);
+        }
+        check_input(coords, lat, lon, height, self.date, self.altitude_range())?;
+        let pos = math::Position::new(lat, lon, height, coords);
+        let lat = pos.slat.atan2(pos.clat).to_degrees();
+        Ok(match frame {
+            MltFrame::Dipole => self.dipole().mlt(lat, lon, instant),
+            MltFrame::Eccentric => self.eccentric_dipole().mlt(lat, lon, pos.r, instant),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_position() {
+        // the March equinox of 2024 was at 03:06 UTC
+        let (lat, _) = subsolar_point(ModelDate::from_ymd_hms(2024, 3, 20, 3, 6, 0.).unwrap());
+        assert!(lat.abs() < 0.01);
+        // the June solstice of 2024 was at 20:51 UTC
+        let (lat, _) = subsolar_point(ModelDate::from_ymd_hms(2024, 6, 20, 20, 51, 0.).unwrap());
+        assert!((lat - 23.44).abs() < 0.01);
+        // at noon on 1 January the sun is 3.3 minutes behind mean time
+        let (lat, lon) = subsolar_point(ModelDate::from_ymd_hms(2024, 1, 1, 12, 0, 0.).unwrap());
+        assert!((lat + 23.0).abs() < 0.1);
+        assert!((lon - 0.8).abs() < 0.1);
+    }
+
+    #[test]
+    fn local_time_of_sun() {
+        let dipole = Dipole::new(-29404.8, -1450.9, 4652.5);
+        let instant = ModelDate::from_ymd_hms(2020, 9, 1, 6, 30, 0.).unwrap();
+        let (lat, lon) = subsolar_point(instant);
+        assert!((dipole.mlt(lat, lon, instant) - 12.).abs() < 1e-9);
+        let (mlat, mlon) = dipole.to_geomagnetic(lat, lon);
+        // an hour later in magnetic time 15 degrees east
+        let (lat, lon) = dipole.from_geomagnetic(mlat, mlon + 15.);
+        assert!((dipole.mlt(lat, lon, instant) - 13.).abs() < 1e-9);
+        // the antisolar point is at magnetic midnight
+        let (lat, lon) = dipole.from_geomagnetic(-mlat, mlon + 180.);
+        let mlt = dipole.mlt(lat, lon, instant);
+        assert!(mlt < 1e-9 || 24. - mlt < 1e-9);
+    }
+}
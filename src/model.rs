@@ -0,0 +1,238 @@
+//! Common interface of the geomagnetic models and a registry to pick them by name.
+use crate::igrf::{IgrfGeneration, IGRF};
+use crate::wmm::{WmmVersion, WMM};
+use crate::{math, CoordinateSystem, FieldGradient, FieldResults, GeomagError, ModelSnapshot};
+
+/// What a model does with dates outside its validity
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Extrapolation {
+    /// Returns [`GeomagError::DateOutOfRange`]
+    #[default]
+    Error,
+    /// Uses the coefficients and annual change at the nearest end of the validity
+    Clamp,
+    /// Continues the coefficients at the nearest end of the validity with their annual
+    /// change, for dates at most `max_years` outside
+    Linear { max_years: f64 },
+}
+
+impl Extrapolation {
+    // Snapshot at `date` where `at` evaluates the dates within `validity`
+    pub(crate) fn snapshot(
+        self,
+        validity: (f64, f64),
+        date: f64,
+        at: impl Fn(f64) -> Result<ModelSnapshot, GeomagError>,
+    ) -> Result<ModelSnapshot, GeomagError> {
+        let (min, max) = validity;
+        if !date.is_finite() {
+            return Err(GeomagError::NonFinite);
+        }
+        if (min..=max).contains(&date) {
+            return at(date);
+        }
+        let edge = date.clamp(min, max);
+        match self {
+            Extrapolation::Error => at(date),
+            Extrapolation::Clamp => Ok(at(edge)?.extrapolate_to(date, 0.)),
+            Extrapolation::Linear { max_years } if (date - edge).abs() <= max_years => {
+                Ok(at(edge)?.extrapolate_to(date, date - edge))
+            }
+            Extrapolation::Linear { max_years } => Err(GeomagError::DateOutOfRange {
+                date,
+                min: min - max_years,
+                max: max + max_years,
+            }),
+        }
+    }
+}
+
+/// A spherical harmonic model of the main geomagnetic field
+pub trait GeomagneticModel: Send + Sync {
+    /// Name of the model, e.g. `IGRF-14` or `WMM2020`
+    fn name(&self) -> &str;
+
+    /// First and last date (decimal years) the model is valid for
+    fn validity(&self) -> (f64, f64);
+
+    /// Lowest and highest altitude (km) the model can be evaluated at
+    fn altitude_range(&self) -> (f64, f64);
+
+    /// Highest spherical harmonic degree of the model
+    fn nmax(&self) -> usize;
+
+    /// Reference radius (km) of the spherical harmonic expansion
+    fn reference_radius(&self) -> f64 {
+        math::EARTHS_RADIUS
+    }
+
+    /// Interpolates the coefficients to `date` (decimal years) for evaluating many positions,
+    /// models outside the crate build the snapshot with [`ModelSnapshot::from_coefficients`]
+    fn try_at(&self, date: f64) -> Result<ModelSnapshot, GeomagError>;
+
+    /// Calculates the field at `lat`/`lon` (degrees) in `coords` and `date` in decimal years,
+    /// `height` is altitude (km) for geodetic and radius (km) for geocentric input
+    fn try_calc_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: f64,
+    ) -> Result<FieldResults, GeomagError>;
+
+    /// Calculates the field at geodetic `lat`/`lon` (degrees), `alt` km above the WGS84
+    /// ellipsoid and `date` in decimal years
+    fn try_calc(
+        &self,
+        lat: f64,
+        lon: f64,
+        alt: f64,
+        date: f64,
+    ) -> Result<FieldResults, GeomagError> {
+        self.try_calc_in(CoordinateSystem::Geodetic, lat, lon, alt, date)
+    }
+
+    /// Spatial derivatives of the main field at `lat`/`lon` (degrees) in `coords` and `date`
+    /// in decimal years, `height` as for [`GeomagneticModel::try_calc_in`]
+    fn try_gradient_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: f64,
+    ) -> Result<FieldGradient, GeomagError>;
+
+    /// Spatial derivatives of the main field at geodetic `lat`/`lon` (degrees), `alt` km
+    /// above the WGS84 ellipsoid and `date` in decimal years
+    fn try_gradient(
+        &self,
+        lat: f64,
+        lon: f64,
+        alt: f64,
+        date: f64,
+    ) -> Result<FieldGradient, GeomagError> {
+        self.try_gradient_in(CoordinateSystem::Geodetic, lat, lon, alt, date)
+    }
+}
+
+/// Collection of models looked up by name
+pub struct Registry {
+    models: Vec<Box<dyn GeomagneticModel>>,
+}
+
+impl Registry {
+    /// An empty registry
+    pub fn empty() -> Registry {
+        Registry { models: vec![] }
+    }
+
+    /// A registry holding every model embedded in the crate
+    pub fn builtin() -> Registry {
+        let mut registry = Registry::empty();
+        for generation in [
+            IgrfGeneration::Igrf11,
+            IgrfGeneration::Igrf12,
+            IgrfGeneration::Igrf13,
+            IgrfGeneration::Igrf14,
+        ] {
+            registry.register(Box::new(IGRF::generation(generation)));
+        }
+        for version in [
+            WmmVersion::Wmm2010,
+            WmmVersion::Wmm2015,
+            WmmVersion::Wmm2015v2,
+            WmmVersion::Wmm2020,
+        ] {
+            registry.register(Box::new(WMM::version(version)));
+        }
+        registry
+    }
+
+    /// Adds a model, replacing any model of the same name
+    pub fn register(&mut self, model: Box<dyn GeomagneticModel>) {
+        self.models
+            .retain(|m| !m.name().eq_ignore_ascii_case(model.name()));
+        self.models.push(model);
+    }
+
+    /// Looks up a model by name, ignoring case
+    pub fn get(&self, name: &str) -> Option<&dyn GeomagneticModel> {
+        self.position(name).map(|i| self.models[i].as_ref())
+    }
+
+    /// Takes a model out of the registry
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn GeomagneticModel>> {
+        self.position(name).map(|i| self.models.remove(i))
+    }
+
+    /// Names of the registered models, in registration order
+    pub fn names(&self) -> Vec<&str> {
+        self.models.iter().map(|m| m.name()).collect()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.models
+            .iter()
+            .position(|m| m.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::builtin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_models_by_name() {
+        let registry = Registry::builtin();
+        assert_eq!(
+            registry.names(),
+            [
+                "IGRF-11",
+                "IGRF-12",
+                "IGRF-13",
+                "IGRF-14",
+                "WMM2010",
+                "WMM2015",
+                "WMM2015v2",
+                "WMM2020"
+            ]
+        );
+        let wmm = registry.get("wmm2020").unwrap();
+        assert_eq!(wmm.validity(), (2020., 2025.));
+        assert_eq!(wmm.nmax(), 12);
+        assert_eq!(wmm.reference_radius(), 6371.2);
+        assert_eq!(registry.get("IGRF-14").unwrap().nmax(), 13);
+        assert!(registry.get("IGRF-15").is_none());
+    }
+
+    #[test]
+    fn models_agree_through_trait() {
+        let registry = Registry::builtin();
+        let igrf = registry.get("IGRF-13").unwrap();
+        let wmm = registry.get("WMM2020").unwrap();
+        let a = igrf.try_calc(59.9, 10.7, 0., 2022.5).unwrap().result;
+        let b = wmm.try_calc(59.9, 10.7, 0., 2022.5).unwrap().result;
+        assert!((a.total_intensity - b.total_intensity).abs() < 100.);
+        assert!((a.declination - b.declination).abs() < 0.5);
+    }
+
+    #[test]
+    fn register_replaces_by_name() {
+        let mut registry = Registry::empty();
+        registry.register(Box::new(IGRF::default().with_name("main")));
+        registry.register(Box::new(
+            IGRF::generation(IgrfGeneration::Igrf12).with_name("main"),
+        ));
+        assert_eq!(registry.names(), ["main"]);
+        assert_eq!(registry.remove("main").unwrap().validity(), (1900., 2020.));
+        assert!(registry.names().is_empty());
+    }
+}
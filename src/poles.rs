@@ -0,0 +1,140 @@
+//! The magnetic dip poles, where the field is vertical, and their drift over the years.
+use std::fmt;
+
+use crate::{
+    math, table, CoordinateSystem, FieldResults, GeomagError, GeomagneticModel, GlobalGrid,
+    LatitudeSpacing, ModelSnapshot,
+};
+
+// Newton steps before giving up, longest step (degrees of arc) and the step (degrees) at
+// which a pole is considered found
+const MAX_ITERATIONS: usize = 50;
+const MAX_STEP: f64 = 2.;
+const TOLERANCE: f64 = 1e-9;
+
+/// Geodetic latitude and longitude (degrees) of both dip poles at one date, where the
+/// horizontal intensity is zero and the inclination ±90 degrees
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DipPoles {
+    /// Where the field points straight down
+    pub north: (f64, f64),
+    /// Where the field points straight up
+    pub south: (f64, f64),
+}
+
+impl ModelSnapshot {
+    /// Locates the dip poles at geodetic altitude `height` (km), starting from the steepest
+    /// inclination on a 2 degree grid and refining with Newton's method on the horizontal
+    /// components
+    pub fn try_dip_poles(&self, height: f64) -> Result<DipPoles, GeomagError> {
+        let grid = GlobalGrid::new(LatitudeSpacing::Equiangular, 91, 180).with_start_lon(-180.);
+        let mut out = vec![FieldResults::default(); grid.len()];
+        self.try_calc_global(CoordinateSystem::Geodetic, &grid, height, &mut out)?;
+        let steepest = |sign: f64| {
+            let (position, _) = grid
+                .positions()
+                .zip(&out)
+                .max_by(|(_, a), (_, b)| {
+                    let (a, b) = (a.result.inclination, b.result.inclination);
+                    (sign * a).total_cmp(&(sign * b))
+                })
+                .expect("the grid is not empty");
+            position
+        };
+        Ok(DipPoles {
+            north: self.refine_dip_pole(height, steepest(1.))?,
+            south: self.refine_dip_pole(height, steepest(-1.))?,
+        })
+    }
+
+    // Newton's method for X = Y = 0 from `start`, stepping along great circles so the
+    // search can pass close to a geographic pole
+    fn refine_dip_pole(&self, height: f64, start: (f64, f64)) -> Result<(f64, f64), GeomagError> {
+        let (mut lat, mut lon) = (start.0.clamp(-89., 89.), start.1);
+        for _ in 0..MAX_ITERATIONS {
+            let field = self.try_calc(lat, lon, height)?.result.orthogonal_strength;
+            let gradient = self.try_gradient_in(CoordinateSystem::Geodetic, lat, lon, height)?;
+            // derivatives of X and Y per degree of arc northwards and eastwards
+            let (slat, clat) = lat.to_radians().sin_cos();
+            let (xn, xe) = (gradient.d_lat[0], gradient.d_lon[0] / clat);
+            let (yn, ye) = (gradient.d_lat[1], gradient.d_lon[1] / clat);
+            let det = xn * ye - xe * yn;
+            let north = (xe * field.east - ye * field.north) / det;
+            let east = (yn * field.north - xn * field.east) / det;
+            let length = north.hypot(east);
+            if !length.is_finite() {
+                return Err(GeomagError::NotConverged);
+            }
+            if length < TOLERANCE {
+                return Ok((lat, lon));
+            }
+            let arc = length.min(MAX_STEP).to_radians();
+            let (slon, clon) = lon.to_radians().sin_cos();
+            let p = math::cartesian(lat, lon);
+            let n = [-slat * clon, -slat * slon, clat];
+            let e = [-slon, clon, 0.];
+            let (sarc, carc) = arc.sin_cos();
+            let moved =
+                [0, 1, 2].map(|i| p[i] * carc + (n[i] * north + e[i] * east) / length * sarc);
+            (lat, lon) = math::spherical(moved);
+        }
+        Err(GeomagError::NotConverged)
+    }
+}
+
+/// Dip poles of a model at a series of dates.
+///
+/// Displays as a table with the altitude on the first line, a column header and one line
+/// per date with the latitude and longitude of the north and south dip pole:
+///
+/// ```text
+/// Dip poles at 0.000 km
+///    DATE  NORTH LAT      LON  SOUTH LAT      LON
+///  2020.0     86.494  162.907    -64.079  135.866
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoleTrack {
+    /// Geodetic altitude (km) of the poles
+    pub height: f64,
+    /// Dates (decimal years) and the poles at them
+    pub rows: Vec<(f64, DipPoles)>,
+}
+
+impl PoleTrack {
+    /// Locates the dip poles of `model` at geodetic altitude `height` (km) for every date
+    pub fn generate<M, I>(model: &M, height: f64, dates: I) -> Result<PoleTrack, GeomagError>
+    where
+        M: GeomagneticModel + ?Sized,
+        I: IntoIterator<Item = f64>,
+    {
+        let rows = dates
+            .into_iter()
+            .map(|date| Ok((date, model.try_at(date)?.try_dip_poles(height)?)))
+            .collect::<Result<Vec<_>, GeomagError>>()?;
+        Ok(PoleTrack { height, rows })
+    }
+
+    /// Dip poles once a year from the start to the end of the validity of `model`
+    pub fn yearly<M>(model: &M, height: f64) -> Result<PoleTrack, GeomagError>
+    where
+        M: GeomagneticModel + ?Sized,
+    {
+        let (start, end) = model.validity();
+        PoleTrack::generate(model, height, table::yearly(start, end, 1.))
+    }
+}
+
+impl fmt::Display for PoleTrack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Dip poles at {:.3} km", self.height)?;
+        writeln!(f, "   DATE  NORTH LAT      LON  SOUTH LAT      LON")?;
+        for (date, poles) in &self.rows {
+            writeln!(
+                f,
+                "{:7.1}{:11.3}{:9.3}{:11.3}{:9.3}",
+                date, poles.north.0, poles.north.1, poles.south.0, poles.south.1
+            )?;
+        }
+        Ok(())
+    }
+}
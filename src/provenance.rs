@@ -0,0 +1,104 @@
+//! Where the results of an evaluation come from, for audit trails.
+use std::sync::Mutex;
+
+use crate::igrf::IgrfGeneration;
+use crate::wmm::WmmVersion;
+
+/// Release of a model
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Generation {
+    /// One of the IGRF generations embedded in the crate
+    Igrf(IgrfGeneration),
+    /// One of the WMM versions embedded in the crate
+    Wmm(WmmVersion),
+    /// Coefficients that are not embedded in the crate, e.g. read from a file
+    #[default]
+    Custom,
+}
+
+/// How the coefficients at the date of a result were found
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DateStatus {
+    /// The date is on the epoch of a definitive model (DGRF) whose coefficients are used
+    /// as they are
+    Definitive,
+    /// Interpolated between the coefficients of two epochs
+    #[default]
+    Interpolated,
+    /// Continued from an epoch with its secular variation within the validity of the model,
+    /// e.g. the last five years of IGRF or any date of WMM
+    Predicted,
+    /// Outside the validity of the model, see [`Extrapolation`](crate::Extrapolation)
+    Extrapolated,
+}
+
+/// Model, coefficients and position behind a result
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Provenance {
+    /// Name of the model, e.g. `IGRF-14`
+    pub model: &'static str,
+    pub generation: Generation,
+    /// Epochs (decimal years) the coefficients were interpolated or predicted from,
+    /// both the same on a definitive epoch
+    pub epochs: (f64, f64),
+    /// Degree of the coefficients at the date
+    pub nmax: usize,
+    pub date_status: DateStatus,
+    /// Set when the latitude is within 0.001 degrees of a pole, where it is moved to
+    /// ±89.999 degrees as in geomag70 and declination is undefined
+    pub near_pole: bool,
+}
+
+impl Provenance {
+    pub(crate) fn new(
+        model: &'static str,
+        generation: Generation,
+        epochs: (f64, f64),
+        nmax: usize,
+        date_status: DateStatus,
+    ) -> Provenance {
+        Provenance {
+            model,
+            generation,
+            epochs,
+            nmax,
+            date_status,
+            near_pole: false,
+        }
+    }
+
+    // The provenance of a result at latitude `lat` (degrees)
+    pub(crate) fn at(&self, lat: f64) -> Provenance {
+        Provenance {
+            near_pole: crate::math::near_pole(lat),
+            ..*self
+        }
+    }
+}
+
+// Names given at runtime, each leaked once so that provenance stays `Copy`. Models are
+// named a handful of times, not per result.
+pub(crate) fn intern(name: &str) -> &'static str {
+    static NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+    let mut names = NAMES.lock().unwrap_or_else(|e| e.into_inner());
+    match names.iter().find(|n| **n == name) {
+        Some(n) => n,
+        None => {
+            let n: &'static str = Box::leak(name.into());
+            names.push(n);
+            n
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interns_names_once() {
+        let name = intern(&String::from("Lithosphere"));
+        assert_eq!(name, "Lithosphere");
+        assert!(std::ptr::eq(name, intern("Lithosphere")));
+    }
+}
@@ -0,0 +1,293 @@
+// Spherical harmonic synthesis of arbitrary degree.
+//
+// Schmidt semi-normalised associated Legendre functions P(n, m) are kept divided by
+// sin(theta)^m. These scaled functions are polynomials in cos(theta) that neither overflow
+// nor underflow for degrees in the hundreds, and the field sums are put back together with
+// a Horner scheme in sin(theta) so the field is never divided by it, not even at the poles.
+//
+// The functions are generated one order at a time while summing, so synthesis needs no
+// tables and does not allocate.
+use crate::math::gh_index;
+
+// Weights of the per order sums: Q, dQ, (n+1) Q, (n+2) Q, (n+2) dQ, (n+1) dQ, (n+1)(n+2) Q
+// and n(n+1) Q, where dQ is the derivative with respect to cos(theta). The field needs
+// the first three, the gradient all of them.
+const FIELD_SUMS: usize = 3;
+const GRADIENT_SUMS: usize = 8;
+
+// Sums of g and h of one order needed for the field
+pub(crate) type OrderSums = ([f64; FIELD_SUMS], [f64; FIELD_SUMS]);
+
+// Point of evaluation in geocentric spherical coordinates
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Point {
+    // cos(theta) and sin(theta) of the colatitude
+    pub(crate) t: f64,
+    pub(crate) u: f64,
+    // longitude (radians)
+    pub(crate) lon: f64,
+    // reference radius over radius
+    pub(crate) ratio: f64,
+}
+
+// Q(m, m) = P(m, m) / sin(theta)^m
+fn sectoral(m: usize) -> f64 {
+    (2..=m)
+        .map(|k| ((2 * k - 1) as f64 / (2 * k) as f64).sqrt())
+        .product()
+}
+
+// Q(m - 1, m - 1) from Q(m, m)
+fn previous_sectoral(m: usize, q: f64) -> f64 {
+    if m > 1 {
+        q / ((2 * m - 1) as f64 / (2 * m) as f64).sqrt()
+    } else {
+        q
+    }
+}
+
+// Calls `f(n, Q, dQ)` for n = m..=nmax, `q_mm` is Q(m, m)
+fn column(m: usize, nmax: usize, t: f64, q_mm: f64, mut f: impl FnMut(usize, f64, f64)) {
+    let (mut q1, mut dq1) = (q_mm, 0.);
+    let (mut q2, mut dq2) = (0., 0.);
+    f(m, q1, dq1);
+    let fm = m as f64;
+    for n in m + 1..=nmax {
+        let fn_ = n as f64;
+        let d = (fn_ * fn_ - fm * fm).sqrt();
+        let a = (2. * fn_ - 1.) / d;
+        let b = ((fn_ - 1.) * (fn_ - 1.) - fm * fm).sqrt() / d;
+        let q = a * t * q1 - b * q2;
+        let dq = a * (q1 + t * dq1) - b * dq2;
+        f(n, q, dq);
+        (q2, dq2, q1, dq1) = (q1, dq1, q, dq);
+    }
+}
+
+// Sums over the degrees of order `m` of g and h times the first K weights,
+// each term scaled by (a / r)^(n + 2)
+pub(crate) fn order_sums<const K: usize>(
+    nmax: usize,
+    gh: &[f64],
+    m: usize,
+    q_mm: f64,
+    t: f64,
+    ratio: f64,
+) -> ([f64; K], [f64; K]) {
+    let mut g = [0.; K];
+    let mut h = [0.; K];
+    let mut rr = ratio.powi(m as i32 + 2);
+    column(m, nmax, t, q_mm, |n, q, dq| {
+        if n > 0 {
+            let (n1, n2) = ((n + 1) as f64, (n + 2) as f64);
+            let weights = [
+                q,
+                dq,
+                n1 * q,
+                n2 * q,
+                n2 * dq,
+                n1 * dq,
+                n1 * n2 * q,
+                n1 * (n1 - 1.) * q,
+            ];
+            let i = gh_index(n, m);
+            for k in 0..K {
+                g[k] += rr * gh[i] * weights[k];
+                if m > 0 {
+                    h[k] += rr * gh[i + 1] * weights[k];
+                }
+            }
+        }
+        rr *= ratio;
+    });
+    (g, h)
+}
+
+// All orders from `nmax` down to 0 as (m, Q(m, m))
+pub(crate) fn orders(nmax: usize) -> impl Iterator<Item = (usize, f64)> {
+    let mut q = sectoral(nmax);
+    (0..=nmax).rev().map(move |m| {
+        let q_mm = q;
+        q = previous_sectoral(m, q);
+        (m, q_mm)
+    })
+}
+
+// Accumulates the field of one order into `acc`, orders must be added from nmax down to 0.
+// dP/dtheta = sin^(m-1) * (m * cos * Q - sin^2 * dQ) and P / sin = sin^(m-1) * Q
+fn add_order(
+    acc: &mut (f64, f64, f64),
+    m: usize,
+    (g, h): &OrderSums,
+    (c, s): (f64, f64),
+    t: f64,
+    u: f64,
+) {
+    let (x, y, z) = acc;
+    let xa = g[0] * c + h[0] * s;
+    let xb = g[1] * c + h[1] * s;
+    let zm = g[2] * c + h[2] * s;
+    if m == 0 {
+        *x -= u * xb;
+    } else {
+        let fm = m as f64;
+        let ym = g[0] * s - h[0] * c;
+        *x = *x * u + fm * t * xa - u * u * xb;
+        *y = *y * u + fm * ym;
+    }
+    *z = *z * u + zm;
+}
+
+// North, east and down components in the geocentric frame for coefficients `gh`
+// in geomag70 ordering, truncated at `nmax`
+pub(crate) fn field(nmax: usize, gh: &[f64], p: &Point) -> (f64, f64, f64) {
+    let sums = orders(nmax)
+        .map(|(m, q_mm)| (m, order_sums::<FIELD_SUMS>(nmax, gh, m, q_mm, p.t, p.ratio)));
+    field_from_sums(sums, p)
+}
+
+// Field from the per order sums given from nmax down to 0, the sums only depend on
+// the colatitude and radius of `p`
+pub(crate) fn field_from_sums(
+    sums: impl Iterator<Item = (usize, OrderSums)>,
+    p: &Point,
+) -> (f64, f64, f64) {
+    let mut acc = (0., 0., 0.);
+    for (m, sums) in sums {
+        let (s, c) = (m as f64 * p.lon).sin_cos();
+        add_order(&mut acc, m, &sums, (c, s), p.t, p.u);
+    }
+    let (x, y, z) = acc;
+    (x, y, -z)
+}
+
+// Coefficients (cos, sin) of cos(m lon) and sin(m lon) in the north, east and down
+// components contributed by the sums of order `m`, the same terms `add_order` accumulates
+pub(crate) fn order_fourier(m: usize, (g, h): &OrderSums, t: f64, u: f64) -> [(f64, f64); 3] {
+    if m == 0 {
+        return [(-u * g[1], 0.), (0., 0.), (-g[2], 0.)];
+    }
+    let fm = m as f64;
+    let um1 = u.powi(m as i32 - 1);
+    let um = um1 * u;
+    [
+        (
+            um1 * (fm * t * g[0] - u * u * g[1]),
+            um1 * (fm * t * h[0] - u * u * h[1]),
+        ),
+        (-um1 * fm * h[0], um1 * fm * g[0]),
+        (-um * g[2], -um * h[2]),
+    ]
+}
+
+// Field like `field` together with its gradient tensor (nT/km) in the geocentric
+// north/east/down frame at radius `r` (km), `tensor[i][j]` is component i along direction j.
+// Second derivatives follow from the Legendre equation
+//   d2P/dtheta2 = sin^(m-2) * (m(m-1) * Q + sin^2 * (m * Q + cos * dQ - n(n+1) * Q))
+// and the gradient is divided by sin(theta), so it is undefined exactly at the poles.
+pub(crate) fn gradient(
+    nmax: usize,
+    gh: &[f64],
+    p: &Point,
+    r: f64,
+) -> ((f64, f64, f64), [[f64; 3]; 3]) {
+    let (t, u) = (p.t, p.u);
+    // sums over m of sin(theta)^m times the bracketed terms, see the end of the loop
+    let mut acc = [0.; 12];
+    for (m, q_mm) in orders(nmax) {
+        let (g, h) = order_sums::<GRADIENT_SUMS>(nmax, gh, m, q_mm, t, p.ratio);
+        let (s, c) = (m as f64 * p.lon).sin_cos();
+        let a = |k: usize| g[k] * c + h[k] * s;
+        let b = |k: usize| g[k] * s - h[k] * c;
+        let (a0, da0, b0, db0) = (a(0), a(1), b(0), b(1));
+        let (a1, b1, a2, b2) = (a(2), b(2), a(3), b(3));
+        let (da2, da1, a12, ann) = (a(4), a(5), a(6), a(7));
+        let fm = m as f64;
+        let u2 = u * u;
+        let brackets = [
+            // X * sin
+            fm * t * a0 - u2 * da0,
+            // Y * sin
+            fm * b0,
+            // -Z
+            a1,
+            // -dX/dr * r * sin
+            fm * t * a2 - u2 * da2,
+            // -dY/dr * r * sin
+            fm * b2,
+            // dZ/dr * r
+            a12,
+            // dX/dtheta * sin^2
+            fm * (fm - 1.) * a0 + u2 * (fm * a0 + t * da0 - ann),
+            // dY/dtheta * sin^2
+            fm * ((fm - 1.) * t * b0 - u2 * db0),
+            // -dZ/dtheta * sin
+            fm * t * a1 - u2 * da1,
+            // -dX/dphi * sin
+            fm * (fm * t * b0 - u2 * db0),
+            // dY/dphi * sin
+            fm * fm * a0,
+            // dZ/dphi
+            fm * b1,
+        ];
+        for (acc, bracket) in acc.iter_mut().zip(brackets) {
+            *acc = *acc * u + bracket;
+        }
+    }
+    let [x, y, z, xr, yr, zr, xt, yt, zt, xp, yp, zp] = acc;
+    let (x, y, z) = (x / u, y / u, -z);
+    let (dx_dr, dy_dr, dz_dr) = (-xr / (r * u), -yr / (r * u), zr / r);
+    let (dx_dt, dy_dt, dz_dt) = (xt / (u * u), yt / (u * u), -zt / u);
+    let (dx_dp, dy_dp, dz_dp) = (-xp / u, yp / u, zp);
+
+    // north is -theta, east is phi and down is -r, plus the terms from the turning frame
+    let ru = r * u;
+    let tensor = [
+        [(-dx_dt - z) / r, (dx_dp + y * t) / ru, -dx_dr],
+        [-dy_dt / r, dy_dp / ru - z / r - x * t / ru, -dy_dr],
+        [(x - dz_dt) / r, dz_dp / ru + y / r, -dz_dr],
+    ];
+    ((x, y, z), tensor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legendre_matches_closed_form() {
+        let theta: f64 = 0.7;
+        let (t, u) = (theta.cos(), theta.sin());
+        let p = |n: usize, m: usize| {
+            let mut value = 0.;
+            column(m, n, t, sectoral(m), |k, q, _| {
+                if k == n {
+                    value = q * u.powi(m as i32);
+                }
+            });
+            value
+        };
+        assert!((p(2, 0) - (1.5 * t * t - 0.5)).abs() < 1e-15);
+        assert!((p(2, 2) - 3f64.sqrt() / 2. * u * u).abs() < 1e-15);
+        assert!((p(3, 1) - (3f64 / 8.).sqrt() * u * (5. * t * t - 1.)).abs() < 1e-15);
+        assert!((p(3, 3) - (5f64 / 8.).sqrt() * u * u * u).abs() < 1e-15);
+        for (m, q_mm) in orders(30) {
+            assert!((q_mm - sectoral(m)).abs() < 1e-14);
+        }
+    }
+
+    #[test]
+    fn high_degree_stays_finite_near_pole() {
+        let nmax = 720;
+        let theta = 1e-6f64;
+        let p = Point {
+            t: theta.cos(),
+            u: theta.sin(),
+            lon: 0.3,
+            ratio: 1.,
+        };
+        let gh = vec![1.; nmax * (nmax + 2)];
+        let (x, y, z) = field(nmax, &gh, &p);
+        assert!(x.is_finite() && y.is_finite() && z.is_finite());
+    }
+}
@@ -0,0 +1,416 @@
+//! A model evaluated at one date, for calculating the field at many positions.
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::error::check_input;
+use crate::fft::{Complex, Fft};
+use crate::igrf::SecularVariation;
+use crate::zones;
+use crate::{
+    math, CoordinateSystem, DateStatus, FieldGradient, FieldResults, Generation, GeomagError,
+    GlobalGrid, Grid, LatitudeSpacing, MagneticCircle, Provenance, ZoneBoundaries,
+};
+
+/// Coefficients of a model interpolated to a fixed date.
+///
+/// Created with [`GeomagneticModel::try_at`](crate::GeomagneticModel::try_at) or from
+/// coefficients with [`ModelSnapshot::from_coefficients`], evaluating a position does not
+/// allocate.
+pub struct ModelSnapshot {
+    pub(crate) date: f64,
+    pub(crate) nmax: usize,
+    // reference radius (km) of the expansion
+    pub(crate) radius: f64,
+    altitude_range: (f64, f64),
+    pub(crate) coeffs: Vec<f64>,
+    // annual change of the coefficients, or the coefficients a year later for
+    // `SecularVariation::FiniteDifference`
+    pub(crate) other: Vec<f64>,
+    pub(crate) sv_mode: SecularVariation,
+    pub(crate) provenance: Provenance,
+}
+
+impl ModelSnapshot {
+    pub(crate) fn new(
+        date: f64,
+        provenance: Provenance,
+        radius: f64,
+        altitude_range: (f64, f64),
+        coeffs: Vec<f64>,
+        other: Vec<f64>,
+        sv_mode: SecularVariation,
+    ) -> ModelSnapshot {
+        ModelSnapshot {
+            date,
+            nmax: provenance.nmax,
+            radius,
+            altitude_range,
+            coeffs,
+            other,
+            sv_mode,
+            provenance,
+        }
+    }
+
+    /// Snapshot of coefficients `coeffs` at `date` (decimal years) in the flat geomag70
+    /// order g(1,0), g(1,1), h(1,1), g(2,0), ... up to degree `nmax`, with their annual
+    /// change `sv` in the same order. `radius` is the reference radius (km) of the
+    /// expansion and `altitude_range` the lowest and highest altitude (km) it may be
+    /// evaluated at.
+    ///
+    /// For implementing [`GeomagneticModel`](crate::GeomagneticModel) outside the crate, the
+    /// provenance of the results names the model with [`ModelSnapshot::with_name`] and
+    /// has [`Generation::Custom`].
+    pub fn from_coefficients(
+        date: f64,
+        nmax: usize,
+        radius: f64,
+        altitude_range: (f64, f64),
+        coeffs: Vec<f64>,
+        sv: Vec<f64>,
+    ) -> Result<ModelSnapshot, GeomagError> {
+        let invalid = |message: String| Err(GeomagError::InvalidModel(message));
+        let needed = nmax
+            .checked_add(2)
+            .and_then(|k| k.checked_mul(nmax))
+            .filter(|_| nmax > 0);
+        if needed.is_none() {
+            return invalid(format!("degree {} is not supported", nmax));
+        }
+        if needed != Some(coeffs.len()) || needed != Some(sv.len()) {
+            return invalid(format!(
+                "degree {} needs {} coefficients, found {} and {} annual changes",
+                nmax,
+                nmax * (nmax + 2),
+                coeffs.len(),
+                sv.len()
+            ));
+        }
+        let (min, max) = altitude_range;
+        if !(date.is_finite() && radius.is_finite())
+            || min.is_nan()
+            || max.is_nan()
+            || coeffs.iter().chain(&sv).any(|c| !c.is_finite())
+        {
+            return Err(GeomagError::NonFinite);
+        }
+        if radius <= 0. {
+            return invalid(format!("reference radius {} km is not positive", radius));
+        }
+        if min > max {
+            return invalid(format!("altitude range {} - {} km is empty", min, max));
+        }
+        let provenance = Provenance::new(
+            "",
+            Generation::Custom,
+            (date, date),
+            nmax,
+            DateStatus::Interpolated,
+        );
+        Ok(ModelSnapshot::new(
+            date,
+            provenance,
+            radius,
+            altitude_range,
+            coeffs,
+            sv,
+            SecularVariation::Analytic,
+        ))
+    }
+
+    /// Names the model in the provenance of the results
+    pub fn with_name(mut self, name: &str) -> ModelSnapshot {
+        self.provenance.model = crate::provenance::intern(name);
+        self
+    }
+
+    // Moves the snapshot to `date` outside the validity, continuing the coefficients for
+    // `years` with their annual change
+    pub(crate) fn extrapolate_to(mut self, date: f64, years: f64) -> ModelSnapshot {
+        for (coeff, other) in self.coeffs.iter_mut().zip(self.other.iter_mut()) {
+            let rate = match self.sv_mode {
+                SecularVariation::Analytic => *other,
+                SecularVariation::FiniteDifference => {
+                    let rate = *other - *coeff;
+                    *other += years * rate;
+                    rate
+                }
+            };
+            *coeff += years * rate;
+        }
+        self.date = date;
+        self.provenance.date_status = DateStatus::Extrapolated;
+        self
+    }
+
+    /// Name of the model the snapshot is taken from, e.g. `IGRF-14`
+    pub fn name(&self) -> &str {
+        self.provenance.model
+    }
+
+    /// Date (decimal years) of the snapshot
+    pub fn date(&self) -> f64 {
+        self.date
+    }
+
+    /// Degree of the coefficients at the date
+    pub fn nmax(&self) -> usize {
+        self.nmax
+    }
+
+    /// Reference radius (km) of the spherical harmonic expansion
+    pub fn reference_radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// Whether the date is outside the validity of the model, see
+    /// [`Extrapolation`](crate::Extrapolation)
+    pub fn extrapolated(&self) -> bool {
+        self.provenance.date_status == DateStatus::Extrapolated
+    }
+
+    /// Model and coefficients behind the snapshot, `near_pole` is never set
+    pub fn provenance(&self) -> &Provenance {
+        &self.provenance
+    }
+
+    /// Lowest and highest altitude (km) the snapshot can be evaluated at
+    pub fn altitude_range(&self) -> (f64, f64) {
+        self.altitude_range
+    }
+
+    /// Calculates the field at geodetic `lat`/`lon` (degrees) and `alt` km above the WGS84
+    /// ellipsoid, panics if the input is invalid, see [`ModelSnapshot::try_calc`]
+    pub fn calc(&self, lat: f64, lon: f64, alt: f64) -> FieldResults {
+        self.try_calc(lat, lon, alt)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Calculates the field, returning an error for input outside what the model supports
+    pub fn try_calc(&self, lat: f64, lon: f64, alt: f64) -> Result<FieldResults, GeomagError> {
+        self.try_calc_in(CoordinateSystem::Geodetic, lat, lon, alt)
+    }
+
+    /// Calculates the field with the position given in `coords`, `height` is altitude (km)
+    /// for geodetic and radius (km) for geocentric input
+    pub fn try_calc_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+    ) -> Result<FieldResults, GeomagError> {
+        check_input(coords, lat, lon, height, self.date, self.altitude_range)?;
+        let pos = math::Position::new(lat, lon, height, coords);
+        let (a, b) = math::shval3(&pos, self.radius, self.nmax, &self.coeffs, &self.other);
+        Ok(FieldResults::new(
+            self.sv_mode,
+            self.provenance.at(lat),
+            a,
+            b,
+        ))
+    }
+
+    /// Spatial derivatives of the main field at the position given in `coords`,
+    /// see [`FieldGradient`]
+    pub fn try_gradient_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+    ) -> Result<FieldGradient, GeomagError> {
+        check_input(coords, lat, lon, height, self.date, self.altitude_range)?;
+        Ok(math::gradient(
+            lat,
+            lon,
+            height,
+            coords,
+            self.radius,
+            self.nmax,
+            &self.coeffs,
+        ))
+    }
+
+    /// Prepares evaluating the field along the circle of latitude `lat` (degrees) at `height`,
+    /// interpreted as for [`ModelSnapshot::try_calc_in`]
+    pub fn try_circle(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        height: f64,
+    ) -> Result<MagneticCircle, GeomagError> {
+        check_input(coords, lat, 0., height, self.date, self.altitude_range)?;
+        Ok(MagneticCircle::new(self, coords, lat, height))
+    }
+
+    /// Calculates the field at every `(lat, lon, height)` of `positions`, interpreted as for
+    /// [`ModelSnapshot::try_calc_in`], into the start of `out`.
+    /// Positions are spread over threads with the `rayon` feature.
+    pub fn try_calc_slice(
+        &self,
+        coords: CoordinateSystem,
+        positions: &[(f64, f64, f64)],
+        out: &mut [FieldResults],
+    ) -> Result<(), GeomagError> {
+        check_output(out, positions.len())?;
+        let calc = |(&(lat, lon, height), out): (&(f64, f64, f64), &mut FieldResults)| {
+            *out = self.try_calc_in(coords, lat, lon, height)?;
+            Ok(())
+        };
+        #[cfg(feature = "rayon")]
+        return positions
+            .par_iter()
+            .zip(out.par_iter_mut())
+            .try_for_each(calc);
+        #[cfg(not(feature = "rayon"))]
+        return positions.iter().zip(out.iter_mut()).try_for_each(calc);
+    }
+
+    /// Calculates the field at the positions of an iterator into the start of `out`,
+    /// see [`ModelSnapshot::try_calc_slice`], returns the number of results written
+    pub fn try_calc_iter<I>(
+        &self,
+        coords: CoordinateSystem,
+        positions: I,
+        out: &mut [FieldResults],
+    ) -> Result<usize, GeomagError>
+    where
+        I: IntoIterator<Item = (f64, f64, f64)>,
+    {
+        let mut positions = positions.into_iter();
+        let mut written = 0;
+        while let Some((lat, lon, height)) = positions.next() {
+            let Some(slot) = out.get_mut(written) else {
+                return Err(GeomagError::OutputTooSmall {
+                    len: out.len(),
+                    needed: written + 1 + positions.count(),
+                });
+            };
+            *slot = self.try_calc_in(coords, lat, lon, height)?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Calculates the field at every position of `grid` into the start of `out`, in the
+    /// order given by [`Grid::index`]. Rows of the grid are spread over threads with the
+    /// `rayon` feature.
+    pub fn try_calc_grid(
+        &self,
+        coords: CoordinateSystem,
+        grid: &Grid,
+        out: &mut [FieldResults],
+    ) -> Result<(), GeomagError> {
+        check_output(out, grid.len())?;
+        if grid.is_empty() {
+            return Ok(());
+        }
+        let row = |(r, row): (usize, &mut [FieldResults])| {
+            let alt = grid.alt.value(r / grid.lat.count);
+            let lat = grid.lat.value(r % grid.lat.count);
+            let circle = self.try_circle(coords, lat, alt)?;
+            for (i, out) in row.iter_mut().enumerate() {
+                *out = circle.try_calc(grid.lon.value(i))?;
+            }
+            Ok(())
+        };
+        let out = &mut out[..grid.len()];
+        #[cfg(feature = "rayon")]
+        return out
+            .par_chunks_mut(grid.lon.count)
+            .enumerate()
+            .try_for_each(row);
+        #[cfg(not(feature = "rayon"))]
+        return out.chunks_mut(grid.lon.count).enumerate().try_for_each(row);
+    }
+
+    /// Calculates the field at every position of a global `grid` at `height`, interpreted as
+    /// for [`ModelSnapshot::try_calc_in`], into the start of `out` in the order given by
+    /// [`GlobalGrid::index`]. Each latitude is synthesised with an FFT over longitude, which
+    /// is much faster than evaluating the positions one by one for fine grids. Longitudes are
+    /// converted to radians exactly, so results differ slightly from [`ModelSnapshot::calc`],
+    /// which truncates the conversion as geomag70 does. Latitudes are spread over threads
+    /// with the `rayon` feature.
+    pub fn try_calc_global(
+        &self,
+        coords: CoordinateSystem,
+        grid: &GlobalGrid,
+        height: f64,
+        out: &mut [FieldResults],
+    ) -> Result<(), GeomagError> {
+        check_output(out, grid.len())?;
+        if grid.is_empty() {
+            return Ok(());
+        }
+        check_input(
+            coords,
+            0.,
+            grid.start_lon(),
+            height,
+            self.date,
+            self.altitude_range,
+        )?;
+        let fft = Fft::new(grid.nlon());
+        let scratch = || vec![Complex::default(); 6 * fft.len() + fft.scratch_len()];
+        let row = |scratch: &mut Vec<Complex>, (&lat, row): (&f64, &mut [FieldResults])| {
+            self.try_circle(coords, lat, height)?
+                .calc_around(&fft, grid.start_lon(), scratch, row);
+            Ok(())
+        };
+        let out = &mut out[..grid.len()];
+        #[cfg(feature = "rayon")]
+        return grid
+            .lats()
+            .par_iter()
+            .zip(out.par_chunks_mut(grid.nlon()))
+            .try_for_each_init(scratch, row);
+        #[cfg(not(feature = "rayon"))]
+        {
+            let mut scratch = scratch();
+            grid.lats()
+                .iter()
+                .zip(out.chunks_mut(grid.nlon()))
+                .try_for_each(|item| row(&mut scratch, item))
+        }
+    }
+
+    /// Boundaries of the compass blackout and caution zones at geodetic altitude `height`
+    /// (km), traced on a grid of `nlat` latitudes from pole to pole and `2 * (nlat - 1)`
+    /// longitudes, see [`ZoneBoundaries`]
+    pub fn try_zone_boundaries(
+        &self,
+        height: f64,
+        nlat: usize,
+    ) -> Result<ZoneBoundaries, GeomagError> {
+        let nlat = nlat.max(3);
+        let grid = GlobalGrid::new(LatitudeSpacing::Equiangular, nlat, 2 * (nlat - 1))
+            .with_start_lon(-180.);
+        let mut out = vec![FieldResults::default(); grid.len()];
+        self.try_calc_global(CoordinateSystem::Geodetic, &grid, height, &mut out)?;
+        let mut h = out
+            .iter()
+            .map(|field| field.result.horizontal_intensity)
+            .collect::<Vec<_>>();
+        // every longitude of the pole rows is the same point
+        for i in [0, nlat - 1] {
+            let row = grid.index(i, 0)..grid.index(i, 0) + grid.nlon();
+            let pole = h[row.start];
+            h[row].fill(pole);
+        }
+        Ok(ZoneBoundaries {
+            blackout: zones::contours(&grid, &h, zones::BLACKOUT_INTENSITY),
+            caution: zones::contours(&grid, &h, zones::CAUTION_INTENSITY),
+        })
+    }
+}
+
+fn check_output(out: &[FieldResults], needed: usize) -> Result<(), GeomagError> {
+    if out.len() < needed {
+        return Err(GeomagError::OutputTooSmall {
+            len: out.len(),
+            needed,
+        });
+    }
+    Ok(())
+}
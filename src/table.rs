@@ -0,0 +1,152 @@
+//! Yearly reference tables at one location, in the format of the tables in `testdata/igrf`.
+use std::fmt;
+
+use crate::{CoordinateSystem, FieldResults, GeomagError, GeomagneticModel};
+
+/// The field at one location for a series of dates.
+///
+/// Displays as a table with the location on the first line, a column header and one line
+/// per date with D, I, H, X, Y, Z and F each followed by its annual change (minutes/yr for
+/// D and I, nT/yr for the rest):
+///
+/// ```text
+/// Lat  59.900 geodetic    Long   39.900     0.000 km
+///    DATE       D   SV      I  SV      H    SV       X    SV       Y    SV       Z    SV      F    SV
+///  1900.5    5.55     4  71.01   0  16646   -10   16568   -12    1610    18   48357   -16  51142   -18
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReferenceTable {
+    /// Coordinate system of the location
+    pub coords: CoordinateSystem,
+    /// Latitude (degrees)
+    pub lat: f64,
+    /// Longitude (degrees)
+    pub lon: f64,
+    /// Altitude, or radius for geocentric input, (km)
+    pub alt: f64,
+    /// Free text after the location on the first line, e.g. the name of the place
+    pub label: String,
+    /// Dates (decimal years) and the field at them
+    pub rows: Vec<(f64, FieldResults)>,
+}
+
+impl ReferenceTable {
+    /// Evaluates `model` at `lat`/`lon` (degrees) and `alt` in `coords` for every date,
+    /// `alt` is altitude (km) for geodetic and radius (km) for geocentric input
+    pub fn generate<M, I>(
+        model: &M,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        alt: f64,
+        dates: I,
+    ) -> Result<ReferenceTable, GeomagError>
+    where
+        M: GeomagneticModel + ?Sized,
+        I: IntoIterator<Item = f64>,
+    {
+        let rows = dates
+            .into_iter()
+            .map(|date| Ok((date, model.try_calc_in(coords, lat, lon, alt, date)?)))
+            .collect::<Result<Vec<_>, GeomagError>>()?;
+        Ok(ReferenceTable {
+            coords,
+            lat,
+            lon,
+            alt,
+            label: String::new(),
+            rows,
+        })
+    }
+
+    /// Sets the text after the location on the first line
+    pub fn with_label(mut self, label: &str) -> ReferenceTable {
+        self.label = label.to_string();
+        self
+    }
+}
+
+/// Dates from `start` to `end` inclusive `step` years apart, e.g. `yearly(1900.5, 2024.5, 1.)`
+/// for the mid-year dates of the tables in `testdata/igrf`
+pub fn yearly(start: f64, end: f64, step: f64) -> impl Iterator<Item = f64> {
+    let valid = start.is_finite() && end.is_finite() && step.is_finite() && step > 0.;
+    let count = if valid && end >= start {
+        // allow for rounding in (end - start) / step like `GridAxis::range`
+        (((end - start) / step + 1e-9).floor() as usize).saturating_add(1)
+    } else {
+        0
+    };
+    (0..count).map(move |i| start + i as f64 * step)
+}
+
+// Rounded to whole minutes or nT as in the tables
+fn whole(value: f64) -> i64 {
+    value.round() as i64
+}
+
+impl fmt::Display for ReferenceTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let coords = match self.coords {
+            CoordinateSystem::Geodetic => "geodetic",
+            CoordinateSystem::Geocentric => "geocentric",
+        };
+        // the tables pad a missing label with spaces
+        let label = if self.label.is_empty() {
+            "                    "
+        } else {
+            &self.label
+        };
+        writeln!(
+            f,
+            "Lat {:7.3} {}    Long {:8.3} {:9.3} km {}",
+            self.lat, coords, self.lon, self.alt, label
+        )?;
+        writeln!(
+            f,
+            "   DATE       D   SV      I  SV      H    SV       X    SV       Y    SV       Z    SV      F    SV"
+        )?;
+        for (date, field) in &self.rows {
+            let (r, sv) = (&field.result, &field.sv);
+            let (o, dot) = (&r.orthogonal_strength, &sv.orthogonal_strength);
+            writeln!(
+                f,
+                "{:7.1}{:8.2}{:6}{:7.2}{:4}{:7}{:6}{:8}{:6}{:8}{:6}{:8}{:6}{:7}{:6}",
+                date,
+                r.declination,
+                whole(sv.declination),
+                r.inclination,
+                whole(sv.inclination),
+                whole(r.horizontal_intensity),
+                whole(sv.horizontal_intensity),
+                whole(o.north),
+                whole(dot.north),
+                whole(o.east),
+                whole(dot.east),
+                whole(o.down),
+                whole(dot.down),
+                whole(r.total_intensity),
+                whole(sv.total_intensity),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yearly_includes_end() {
+        assert_eq!(
+            yearly(1900.5, 1903.5, 1.).collect::<Vec<_>>(),
+            [1900.5, 1901.5, 1902.5, 1903.5]
+        );
+        assert_eq!(yearly(2000., 2001., 0.1).count(), 11);
+        assert_eq!(yearly(2000., 1999., 1.).count(), 0);
+        assert_eq!(yearly(2000., 2010., 0.).count(), 0);
+        assert_eq!(yearly(2000., f64::INFINITY, 1.).count(), 0);
+        assert_eq!(yearly(2000., 2010., f64::NAN).count(), 0);
+        assert_eq!(yearly(2000., 2010., 1e-300).size_hint().0, usize::MAX);
+    }
+}
@@ -0,0 +1,455 @@
+//! Tracing magnetic field lines through a model.
+use crate::error::check_input;
+use crate::math;
+use crate::{sh, CoordinateSystem, GeomagError, ModelSnapshot};
+
+// First step (km) and the step (km) below which a line is given up
+const INITIAL_STEP: f64 = 10.;
+const MIN_STEP: f64 = 1e-6;
+// Bisections locating the crossing of the stop altitude, and how close (km) is enough
+const MAX_BISECTIONS: usize = 60;
+const ALTITUDE_TOLERANCE: f64 = 1e-6;
+
+// Dormand-Prince 5(4) tableau, the field does not depend on the arc length so the nodes
+// are not needed. The fourth order weights give the error estimate.
+const A: [[f64; 6]; 7] = [
+    [0., 0., 0., 0., 0., 0.],
+    [1. / 5., 0., 0., 0., 0., 0.],
+    [3. / 40., 9. / 40., 0., 0., 0., 0.],
+    [44. / 45., -56. / 15., 32. / 9., 0., 0., 0.],
+    [
+        19372. / 6561.,
+        -25360. / 2187.,
+        64448. / 6561.,
+        -212. / 729.,
+        0.,
+        0.,
+    ],
+    [
+        9017. / 3168.,
+        -355. / 33.,
+        46732. / 5247.,
+        49. / 176.,
+        -5103. / 18656.,
+        0.,
+    ],
+    [
+        35. / 384.,
+        0.,
+        500. / 1113.,
+        125. / 192.,
+        -2187. / 6784.,
+        11. / 84.,
+    ],
+];
+const B5: [f64; 7] = [
+    35. / 384.,
+    0.,
+    500. / 1113.,
+    125. / 192.,
+    -2187. / 6784.,
+    11. / 84.,
+    0.,
+];
+const B4: [f64; 7] = [
+    5179. / 57600.,
+    0.,
+    7571. / 16695.,
+    393. / 640.,
+    -92097. / 339200.,
+    187. / 2100.,
+    1. / 40.,
+];
+
+/// Which way to follow the field from the start point
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceDirection {
+    /// Along the field, from the southern towards the northern magnetic hemisphere
+    Along,
+    /// Against the field, from the northern towards the southern magnetic hemisphere
+    Against,
+}
+
+/// Why tracing a field line stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceStop {
+    /// The line came down through the stop altitude, the last point is on it
+    Altitude,
+    /// The line reached the magnetic equator, the last point is where |B| is smallest
+    Equator,
+    /// The maximum number of steps was taken
+    MaxSteps,
+    /// The next step would leave the altitude range of the model
+    AltitudeRange,
+}
+
+/// Settings of field line tracing, see [`ModelSnapshot::try_trace`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceOptions {
+    direction: TraceDirection,
+    stop_altitude: f64,
+    stop_at_equator: bool,
+    max_steps: usize,
+    tolerance: f64,
+    max_step: f64,
+    output: CoordinateSystem,
+}
+
+impl Default for TraceOptions {
+    /// Along the field down to the ellipsoid, in at most 10000 steps of up to 500 km with
+    /// an error of at most 1 m each, output in geodetic coordinates
+    fn default() -> Self {
+        TraceOptions {
+            direction: TraceDirection::Along,
+            stop_altitude: 0.,
+            stop_at_equator: false,
+            max_steps: 10000,
+            tolerance: 1e-3,
+            max_step: 500.,
+            output: CoordinateSystem::Geodetic,
+        }
+    }
+}
+
+impl TraceOptions {
+    /// Sets which way to follow the field
+    pub fn with_direction(mut self, direction: TraceDirection) -> TraceOptions {
+        self.direction = direction;
+        self
+    }
+
+    /// Stops where the line comes down through geodetic altitude `alt` (km)
+    pub fn with_stop_altitude(mut self, alt: f64) -> TraceOptions {
+        self.stop_altitude = alt;
+        self
+    }
+
+    /// Stops at the magnetic equator, where |B| along the line is smallest
+    pub fn with_stop_at_equator(mut self, stop: bool) -> TraceOptions {
+        self.stop_at_equator = stop;
+        self
+    }
+
+    /// Stops after `steps` steps
+    pub fn with_max_steps(mut self, steps: usize) -> TraceOptions {
+        self.max_steps = steps;
+        self
+    }
+
+    /// Sets the largest position error (km) allowed in one step, and the longest step (km),
+    /// both positive and finite or tracing returns [`GeomagError::InvalidAccuracy`]
+    pub fn with_accuracy(mut self, tolerance: f64, max_step: f64) -> TraceOptions {
+        self.tolerance = tolerance;
+        self.max_step = max_step;
+        self
+    }
+
+    /// Sets the coordinates of the points of the traced line
+    pub fn with_output(mut self, coords: CoordinateSystem) -> TraceOptions {
+        self.output = coords;
+        self
+    }
+}
+
+/// A traced field line
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldLine {
+    /// Coordinates of the points
+    pub coords: CoordinateSystem,
+    /// Latitude, longitude (degrees) and altitude (km) for geodetic or radius (km) for
+    /// geocentric coordinates of the start point and every step
+    pub points: Vec<(f64, f64, f64)>,
+    /// Length (km) of the line
+    pub length: f64,
+    pub stop: TraceStop,
+}
+
+impl ModelSnapshot {
+    /// Traces the field line through the position given in `coords`, `height` is altitude
+    /// (km) for geodetic and radius (km) for geocentric input. Steps with adaptive
+    /// Runge-Kutta (Dormand-Prince 5(4)) until one of the conditions of `options` is met.
+    pub fn try_trace(
+        &self,
+        options: &TraceOptions,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+    ) -> Result<FieldLine, GeomagError> {
+        check_input(coords, lat, lon, height, self.date, self.altitude_range())?;
+        let (tolerance, max_step) = (options.tolerance, options.max_step);
+        if !(tolerance > 0. && tolerance.is_finite() && max_step > 0. && max_step.is_finite()) {
+            return Err(GeomagError::InvalidAccuracy {
+                tolerance,
+                max_step,
+            });
+        }
+        let sign = match options.direction {
+            TraceDirection::Along => 1.,
+            TraceDirection::Against => -1.,
+        };
+        let mut p = match coords {
+            CoordinateSystem::Geodetic => math::geodetic_to_cartesian(lat, lon, height),
+            CoordinateSystem::Geocentric => math::cartesian(lat, lon).map(|c| c * height),
+        };
+        let (min_alt, max_alt) = self.altitude_range();
+        let mut points = vec![p];
+        let mut length = 0.;
+        let mut strength = math::norm(self.cartesian_field(p));
+        // arc length and |B| at the point before `p` while |B| is falling
+        let mut falling: Option<(f64, f64)> = None;
+        let mut h = INITIAL_STEP.min(options.max_step);
+        let stop = loop {
+            if points.len() > options.max_steps {
+                break TraceStop::MaxSteps;
+            }
+            let (next, error) = self.trace_step(sign, p, h);
+            if error.is_nan() || error > options.tolerance {
+                h *= (0.9 * (options.tolerance / error).powf(0.2)).clamp(0.2, 1.);
+                if h.is_nan() || h < MIN_STEP {
+                    return Err(GeomagError::NotConverged);
+                }
+                continue;
+            }
+            let alt = math::cartesian_to_geodetic(next).2;
+            if alt < options.stop_altitude
+                && math::cartesian_to_geodetic(p).2 >= options.stop_altitude
+            {
+                let end = self.altitude_crossing(sign, p, h, options.stop_altitude);
+                points.push(end.0);
+                length += end.1;
+                break TraceStop::Altitude;
+            }
+            if !(min_alt..=max_alt).contains(&alt) {
+                break TraceStop::AltitudeRange;
+            }
+            let next_strength = math::norm(self.cartesian_field(next));
+            if options.stop_at_equator && next_strength > strength {
+                if let Some(before) = falling {
+                    let (point, at) = self.equator(
+                        sign,
+                        p,
+                        before,
+                        (length, strength),
+                        (length + h, next_strength),
+                    );
+                    if at < length {
+                        points.pop();
+                    }
+                    points.push(point);
+                    length = at;
+                    break TraceStop::Equator;
+                }
+            }
+            falling = (next_strength < strength).then_some((length, strength));
+            points.push(next);
+            length += h;
+            p = next;
+            strength = next_strength;
+            let grow = if error > 0. {
+                0.9 * (options.tolerance / error).powf(0.2)
+            } else {
+                5.
+            };
+            h = (h * grow.clamp(1., 5.)).min(options.max_step);
+        };
+        let points = points
+            .into_iter()
+            .map(|p| match options.output {
+                CoordinateSystem::Geodetic => math::cartesian_to_geodetic(p),
+                CoordinateSystem::Geocentric => {
+                    let (lat, lon) = math::spherical(p);
+                    (lat, lon, math::norm(p))
+                }
+            })
+            .collect();
+        Ok(FieldLine {
+            coords: options.output,
+            points,
+            length,
+            stop,
+        })
+    }
+
+    // Main field (nT) at the geocentric cartesian position `p` (km) in cartesian components
+    fn cartesian_field(&self, p: [f64; 3]) -> [f64; 3] {
+        let r = math::norm(p);
+        let rho = p[0].hypot(p[1]);
+        let lon = p[1].atan2(p[0]);
+        let point = sh::Point {
+            t: p[2] / r,
+            u: rho / r,
+            lon,
+            ratio: self.radius / r,
+        };
+        let (x, y, z) = sh::field(self.nmax, &self.coeffs, &point);
+        let (slon, clon) = lon.sin_cos();
+        let (slat, clat) = (point.t, point.u);
+        let north = [-slat * clon, -slat * slon, clat];
+        let east = [-slon, clon, 0.];
+        let up = [clat * clon, clat * slon, slat];
+        [0, 1, 2].map(|i| x * north[i] + y * east[i] - z * up[i])
+    }
+
+    // One Dormand-Prince step of `h` km from `p` along the unit field times `sign`,
+    // returning the new position and an estimate of its error (km)
+    fn trace_step(&self, sign: f64, p: [f64; 3], h: f64) -> ([f64; 3], f64) {
+        let mut k = [[0.; 3]; 7];
+        for stage in 0..7 {
+            let at = [0, 1, 2]
+                .map(|i| p[i] + h * (0..stage).map(|j| A[stage][j] * k[j][i]).sum::<f64>());
+            let b = self.cartesian_field(at);
+            let scale = sign / math::norm(b);
+            k[stage] = b.map(|c| c * scale);
+        }
+        let next = [0, 1, 2].map(|i| p[i] + h * (0..7).map(|j| B5[j] * k[j][i]).sum::<f64>());
+        let error = [0, 1, 2].map(|i| h * (0..7).map(|j| (B5[j] - B4[j]) * k[j][i]).sum::<f64>());
+        (next, math::norm(error))
+    }
+
+    // Point and arc length (km) from `p` where a step of at most `h` comes down to `level`
+    fn altitude_crossing(&self, sign: f64, p: [f64; 3], h: f64, level: f64) -> ([f64; 3], f64) {
+        let (mut low, mut high) = (0., h);
+        let mut end = (self.trace_step(sign, p, h).0, h);
+        for _ in 0..MAX_BISECTIONS {
+            let mid = 0.5 * (low + high);
+            let point = self.trace_step(sign, p, mid).0;
+            let alt = math::cartesian_to_geodetic(point).2;
+            end = (point, mid);
+            if (alt - level).abs() < ALTITUDE_TOLERANCE {
+                break;
+            }
+            if alt < level {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        end
+    }
+
+    // Where |B| is smallest near `p`, from a parabola through (arc length, |B|) before, at
+    // and after `p`. Returns the point and its arc length.
+    fn equator(
+        &self,
+        sign: f64,
+        p: [f64; 3],
+        (sa, ba): (f64, f64),
+        (sb, bb): (f64, f64),
+        (sc, bc): (f64, f64),
+    ) -> ([f64; 3], f64) {
+        let numerator = (sb - sa).powi(2) * (bb - bc) - (sb - sc).powi(2) * (bb - ba);
+        let denominator = (sb - sa) * (bb - bc) - (sb - sc) * (bb - ba);
+        let at = (sb - 0.5 * numerator / denominator).clamp(sa, sc);
+        (self.trace_step(sign, p, at - sb).0, at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::igrf::SecularVariation;
+    use crate::math::EARTHS_RADIUS;
+    use crate::{DateStatus, Generation, Provenance};
+
+    // An axial dipole, whose field lines are r = L cos²(lat)
+    fn axial_dipole() -> ModelSnapshot {
+        let provenance = Provenance::new(
+            "dipole",
+            Generation::Custom,
+            (2020., 2020.),
+            1,
+            DateStatus::Interpolated,
+        );
+        ModelSnapshot::new(
+            2020.,
+            provenance,
+            EARTHS_RADIUS,
+            (-1., f64::INFINITY),
+            vec![-30000., 0., 0.],
+            vec![0.; 3],
+            SecularVariation::Analytic,
+        )
+    }
+
+    #[test]
+    fn dipole_field_line() {
+        let snapshot = axial_dipole();
+        let l = 2. * EARTHS_RADIUS;
+        let options = TraceOptions::default().with_output(CoordinateSystem::Geocentric);
+        let line = snapshot
+            .try_trace(&options, CoordinateSystem::Geocentric, 0., 30., l)
+            .unwrap();
+        assert_eq!(line.stop, TraceStop::Altitude);
+        // along the field from the equator goes north
+        let &(lat, lon, r) = line.points.last().unwrap();
+        assert!(lat > 40. && lat < 50.);
+        for &(lat, lon, r) in &line.points {
+            assert!((r / lat.to_radians().cos().powi(2) / l - 1.).abs() < 1e-6);
+            assert!((lon - 30.).abs() < 1e-9);
+        }
+        let end = math::cartesian(lat, lon).map(|c| c * r);
+        assert!(math::cartesian_to_geodetic(end).2.abs() < 1e-5);
+        // arc length of the dipole line from the equator to `lat`
+        let s = |lat: f64| {
+            let x = lat.to_radians().sin();
+            let root = (1. + 3. * x * x).sqrt();
+            l / 2. * (x * root + (3f64.sqrt() * x + root).ln() / 3f64.sqrt())
+        };
+        assert!((line.length - s(lat)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn dipole_equator() {
+        let snapshot = axial_dipole();
+        let options = TraceOptions::default()
+            .with_direction(TraceDirection::Against)
+            .with_stop_at_equator(true)
+            .with_output(CoordinateSystem::Geocentric);
+        let line = snapshot
+            .try_trace(
+                &options,
+                CoordinateSystem::Geocentric,
+                40.,
+                -60.,
+                EARTHS_RADIUS,
+            )
+            .unwrap();
+        assert_eq!(line.stop, TraceStop::Equator);
+        let &(lat, _, r) = line.points.last().unwrap();
+        assert!(lat.abs() < 1e-3);
+        let l = EARTHS_RADIUS / 40f64.to_radians().cos().powi(2);
+        assert!((r / l - 1.).abs() < 1e-6);
+
+        let short = snapshot
+            .try_trace(
+                &options.with_max_steps(3),
+                CoordinateSystem::Geocentric,
+                40.,
+                -60.,
+                EARTHS_RADIUS,
+            )
+            .unwrap();
+        assert_eq!(short.stop, TraceStop::MaxSteps);
+        assert_eq!(short.points.len(), 4);
+    }
+
+    #[test]
+    fn rejects_invalid_accuracy() {
+        let snapshot = axial_dipole();
+        for (tolerance, max_step) in [
+            (0., 500.),
+            (-1e-3, 500.),
+            (f64::NAN, 500.),
+            (1e-3, 0.),
+            (1e-3, -500.),
+            (1e-3, f64::INFINITY),
+        ] {
+            let options = TraceOptions::default().with_accuracy(tolerance, max_step);
+            assert!(matches!(
+                snapshot.try_trace(&options, CoordinateSystem::Geodetic, 45., 0., 0.),
+                Err(GeomagError::InvalidAccuracy { .. })
+            ));
+        }
+    }
+}
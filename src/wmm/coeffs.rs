@@ -0,0 +1,95 @@
+use crate::GeomagError;
+
+const WMM2010: &str = include_str!("../../coeffs/cof/WMM2010.COF");
+const WMM2015: &str = include_str!("../../coeffs/cof/WMM2015.COF.1");
+const WMM2015V2: &str = include_str!("../../coeffs/cof/WMM2015v2.COF");
+const WMM2020: &str = include_str!("../../coeffs/cof/WMM2020.COF");
+
+/// Years a WMM release is valid for, counted from its epoch
+const VALIDITY: f64 = 5.;
+
+pub(crate) struct WMMCoeffs {
+    pub(crate) epoch: f64,
+    pub(crate) nmax: usize,
+    // Main field at epoch, geomag70 ordering (g10, g11, h11, g20, ...)
+    main: Vec<f64>,
+    // Secular variation (nT/year), same ordering as main
+    sv: Vec<f64>,
+}
+
+pub fn wmm_data(version: super::WmmVersion) -> WMMCoeffs {
+    let cof = match version {
+        super::WmmVersion::Wmm2010 => WMM2010,
+        super::WmmVersion::Wmm2015 => WMM2015,
+        super::WmmVersion::Wmm2015v2 => WMM2015V2,
+        super::WmmVersion::Wmm2020 => WMM2020,
+    };
+
+    let mut lines = cof.lines().filter(|s| !s.trim().is_empty());
+    let epoch = lines
+        .next()
+        .and_then(|hdr| hdr.split_whitespace().next())
+        .and_then(|e| e.parse::<f64>().ok())
+        .unwrap();
+
+    let rows = lines
+        .take_while(|s| !s.starts_with("9999"))
+        .map(|s| s.split_whitespace().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let nmax = rows
+        .iter()
+        .map(|r| r[0].parse::<usize>().unwrap())
+        .max()
+        .unwrap();
+
+    let mut main = vec![0.; nmax * (nmax + 2)];
+    let mut sv = vec![0.; nmax * (nmax + 2)];
+    for row in rows {
+        let n = row[0].parse::<usize>().unwrap();
+        let m = row[1].parse::<usize>().unwrap();
+        let values = row[2..6]
+            .iter()
+            .map(|v| v.parse::<f64>().unwrap())
+            .collect::<Vec<_>>();
+        let g = crate::math::gh_index(n, m);
+        main[g] = values[0];
+        sv[g] = values[2];
+        if m > 0 {
+            main[g + 1] = values[1];
+            sv[g + 1] = values[3];
+        }
+    }
+
+    WMMCoeffs {
+        epoch,
+        nmax,
+        main,
+        sv,
+    }
+}
+
+impl WMMCoeffs {
+    pub(crate) fn valid_until(&self) -> f64 {
+        self.epoch + VALIDITY
+    }
+
+    // Returns the coefficients at `date` together with their annual change
+    pub(crate) fn coeffs(&self, date: f64) -> Result<(Vec<f64>, &[f64]), GeomagError> {
+        if !(self.epoch..=self.valid_until()).contains(&date) {
+            return Err(GeomagError::DateOutOfRange {
+                date,
+                min: self.epoch,
+                max: self.valid_until(),
+            });
+        }
+        let dt = date - self.epoch;
+        let coeffs = self
+            .main
+            .iter()
+            .zip(self.sv.iter())
+            .map(|(g, sv)| g + dt * sv)
+            .collect();
+        Ok((coeffs, &self.sv))
+    }
+}
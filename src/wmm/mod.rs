@@ -0,0 +1,202 @@
+use crate::igrf::SecularVariation;
+use crate::{
+    CoordinateSystem, DateStatus, Extrapolation, FieldGradient, FieldResults, Generation,
+    GeomagError, GeomagneticModel, ModelDate, ModelSnapshot, Provenance,
+};
+
+mod coeffs;
+
+/// Released versions of the World Magnetic Model
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WmmVersion {
+    /// WMM2010, valid 2010.0 - 2015.0
+    Wmm2010,
+    /// WMM2015, valid 2015.0 - 2020.0
+    Wmm2015,
+    /// Out-of-cycle WMM2015 update, valid 2015.0 - 2020.0
+    Wmm2015v2,
+    /// WMM2020, valid 2020.0 - 2025.0
+    Wmm2020,
+}
+
+// Altitudes the WMM is specified for
+const ALTITUDE_RANGE: (f64, f64) = (-1., 850.);
+
+impl WmmVersion {
+    /// Name of the release, e.g. `WMM2020`
+    pub fn name(self) -> &'static str {
+        match self {
+            WmmVersion::Wmm2010 => "WMM2010",
+            WmmVersion::Wmm2015 => "WMM2015",
+            WmmVersion::Wmm2015v2 => "WMM2015v2",
+            WmmVersion::Wmm2020 => "WMM2020",
+        }
+    }
+}
+
+pub type WMMresults = FieldResults;
+
+pub struct WMM {
+    version: WmmVersion,
+    coeffs: coeffs::WMMCoeffs,
+    extrapolation: Extrapolation,
+}
+impl Default for WMM {
+    fn default() -> WMM {
+        WMM::version(WmmVersion::Wmm2020)
+    }
+}
+impl WMM {
+    pub fn version(version: WmmVersion) -> WMM {
+        WMM {
+            version,
+            coeffs: coeffs::wmm_data(version),
+            extrapolation: Extrapolation::Error,
+        }
+    }
+
+    /// Selects what happens to dates outside the validity, [`Extrapolation::Error`] by default
+    pub fn with_extrapolation(mut self, extrapolation: Extrapolation) -> WMM {
+        self.extrapolation = extrapolation;
+        self
+    }
+
+    /// Epoch of the model in decimal years
+    pub fn epoch(&self) -> f64 {
+        self.coeffs.epoch
+    }
+
+    /// Last date (decimal years) the model is valid for
+    pub fn valid_until(&self) -> f64 {
+        self.coeffs.valid_until()
+    }
+
+    /// Lowest and highest altitude (km) the model can be evaluated at
+    pub fn altitude_range(&self) -> (f64, f64) {
+        ALTITUDE_RANGE
+    }
+
+    /// Interpolates the coefficients to `date` (decimal years or a [`ModelDate`]) for
+    /// evaluating many positions, panics if the date is outside the model, see [`WMM::try_at`]
+    pub fn at(&self, date: impl Into<ModelDate>) -> ModelSnapshot {
+        self.try_at(date).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Interpolates the coefficients to `date`, returning an error outside the model validity
+    pub fn try_at(&self, date: impl Into<ModelDate>) -> Result<ModelSnapshot, GeomagError> {
+        let date = date.into().decimal_year();
+        let validity = (self.epoch(), self.valid_until());
+        self.extrapolation
+            .snapshot(validity, date, |date| self.snapshot(date))
+    }
+
+    // Snapshot at a date within the validity
+    fn snapshot(&self, date: f64) -> Result<ModelSnapshot, GeomagError> {
+        let (coeffs, sv) = self.coeffs.coeffs(date)?;
+        let provenance = Provenance::new(
+            self.version.name(),
+            Generation::Wmm(self.version),
+            (self.epoch(), self.valid_until()),
+            self.coeffs.nmax,
+            DateStatus::Predicted,
+        );
+        Ok(ModelSnapshot::new(
+            date,
+            provenance,
+            self.reference_radius(),
+            self.altitude_range(),
+            coeffs,
+            sv.to_vec(),
+            SecularVariation::Analytic,
+        ))
+    }
+
+    /// Calculates the field at geodetic `lat`/`lon` (degrees), `alt` km above the WGS84 ellipsoid
+    /// and `date` in decimal years or as a [`ModelDate`], panics if the input is invalid,
+    /// see [`WMM::try_calc`]
+    pub fn calc(&self, lat: f64, lon: f64, alt: f64, date: impl Into<ModelDate>) -> WMMresults {
+        self.try_calc(lat, lon, alt, date)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Calculates the field, returning an error for input outside what the model supports
+    pub fn try_calc(
+        &self,
+        lat: f64,
+        lon: f64,
+        alt: f64,
+        date: impl Into<ModelDate>,
+    ) -> Result<WMMresults, GeomagError> {
+        self.try_calc_in(CoordinateSystem::Geodetic, lat, lon, alt, date)
+    }
+
+    /// Calculates the field like [`WMM::try_calc`] with the position given in `coords`,
+    /// `height` is altitude (km) for geodetic and radius (km) for geocentric input
+    pub fn try_calc_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: impl Into<ModelDate>,
+    ) -> Result<WMMresults, GeomagError> {
+        self.try_at(date)?.try_calc_in(coords, lat, lon, height)
+    }
+
+    /// Spatial derivatives of the main field at the position given in `coords`,
+    /// see [`FieldGradient`]
+    pub fn try_gradient_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: impl Into<ModelDate>,
+    ) -> Result<FieldGradient, GeomagError> {
+        self.try_at(date)?.try_gradient_in(coords, lat, lon, height)
+    }
+}
+
+impl GeomagneticModel for WMM {
+    fn name(&self) -> &str {
+        self.version.name()
+    }
+
+    fn validity(&self) -> (f64, f64) {
+        (self.epoch(), self.valid_until())
+    }
+
+    fn altitude_range(&self) -> (f64, f64) {
+        WMM::altitude_range(self)
+    }
+
+    fn nmax(&self) -> usize {
+        self.coeffs.nmax
+    }
+
+    fn try_at(&self, date: f64) -> Result<ModelSnapshot, GeomagError> {
+        WMM::try_at(self, date)
+    }
+
+    fn try_calc_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: f64,
+    ) -> Result<FieldResults, GeomagError> {
+        WMM::try_calc_in(self, coords, lat, lon, height, date)
+    }
+
+    fn try_gradient_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: f64,
+    ) -> Result<FieldGradient, GeomagError> {
+        WMM::try_gradient_in(self, coords, lat, lon, height, date)
+    }
+}
@@ -0,0 +1,237 @@
+//! Areas around the magnetic poles where a compass is unreliable, as defined in the
+//! WMM specification.
+use std::collections::BTreeMap;
+
+use crate::GlobalGrid;
+
+/// Horizontal intensity (nT) below which a compass is unusable
+pub const BLACKOUT_INTENSITY: f64 = 2000.;
+/// Horizontal intensity (nT) below which a compass may be degraded
+pub const CAUTION_INTENSITY: f64 = 6000.;
+
+/// Reliability of a compass at a location, from the horizontal intensity
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompassZone {
+    /// At least 6000 nT
+    Normal,
+    /// 2000 nT up to 6000 nT, the compass may be degraded
+    Caution,
+    /// Below 2000 nT, or not a number, the compass is unreliable
+    Blackout,
+}
+
+impl CompassZone {
+    /// Classifies horizontal intensity `h` (nT)
+    pub fn from_horizontal_intensity(h: f64) -> CompassZone {
+        if h.is_nan() || h < BLACKOUT_INTENSITY {
+            CompassZone::Blackout
+        } else if h < CAUTION_INTENSITY {
+            CompassZone::Caution
+        } else {
+            CompassZone::Normal
+        }
+    }
+}
+
+/// Boundaries of the blackout and caution zones at one date.
+///
+/// Each polygon is a ring of (lat, lon) points in degrees, the last point connecting back
+/// to the first, with the zone on its left. Longitudes are continuous along a ring and may
+/// leave -180 to 180. A zone that contains a geographic pole is closed along the pole.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ZoneBoundaries {
+    /// Areas where the horizontal intensity is below 2000 nT
+    pub blackout: Vec<Vec<(f64, f64)>>,
+    /// Areas where the horizontal intensity is below 6000 nT, containing the blackout zones
+    pub caution: Vec<Vec<(f64, f64)>>,
+}
+
+// A grid edge crossed by a contour, `Along` runs from longitude j to j + 1 at latitude i,
+// `Across` from latitude i to i + 1 at longitude j
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Edge {
+    Along(usize, usize),
+    Across(usize, usize),
+}
+
+// Values on an equiangular global grid shifted so the contour is at zero
+struct Field<'a> {
+    grid: &'a GlobalGrid,
+    values: &'a [f64],
+    level: f64,
+}
+
+impl Field<'_> {
+    fn value(&self, i: usize, j: usize) -> f64 {
+        self.values[self.grid.index(i, j % self.grid.nlon())] - self.level
+    }
+
+    fn inside(&self, i: usize, j: usize) -> bool {
+        self.value(i, j) < 0.
+    }
+
+    // Where the contour crosses `edge`, interpolated linearly
+    fn crossing(&self, edge: Edge) -> (f64, f64) {
+        let lats = self.grid.lats();
+        let step = 360. / self.grid.nlon() as f64;
+        let ((i, j), (k, l), (dlat, dlon)) = match edge {
+            Edge::Along(i, j) => ((i, j), (i, j + 1), (0., step)),
+            Edge::Across(i, j) => ((i, j), (i + 1, j), (lats[i + 1] - lats[i], 0.)),
+        };
+        let (a, b) = (self.value(i, j), self.value(k, l));
+        let t = a / (a - b);
+        (lats[i] + t * dlat, self.grid.lon(j) + t * dlon)
+    }
+}
+
+// Contours of `values` at `level` on an equiangular `grid`, as rings with the values
+// below the level on their left. The latitude rows at the poles must be constant.
+pub(crate) fn contours(grid: &GlobalGrid, values: &[f64], level: f64) -> Vec<Vec<(f64, f64)>> {
+    let field = Field {
+        grid,
+        values,
+        level,
+    };
+    let nlon = grid.nlon();
+    // each crossing starts the segment in the cell where the contour leaves the zone
+    // when going counterclockwise around the cell, and ends the one in the neighbour
+    let mut next = BTreeMap::new();
+    for i in 0..grid.lats().len().saturating_sub(1) {
+        for j in 0..nlon {
+            let corners = [(i, j), (i, j + 1), (i + 1, j + 1), (i + 1, j)];
+            let edges = [
+                Edge::Along(i, j),
+                Edge::Across(i, (j + 1) % nlon),
+                Edge::Along(i + 1, j),
+                Edge::Across(i, j),
+            ];
+            let inside = corners.map(|(i, j)| field.inside(i, j));
+            let crossings = (0..4)
+                .filter(|&k| inside[k] != inside[(k + 1) % 4])
+                .collect::<Vec<_>>();
+            if crossings.is_empty() {
+                continue;
+            }
+            // a saddle joins the zone through the centre if the centre is inside
+            let center = corners.iter().map(|&(i, j)| field.value(i, j)).sum::<f64>();
+            let offset = if center < 0. { 1 } else { crossings.len() - 1 };
+            for (p, &k) in crossings.iter().enumerate() {
+                if inside[k] {
+                    let end = crossings[(p + offset) % crossings.len()];
+                    next.insert(edges[k], edges[end]);
+                }
+            }
+        }
+    }
+    let mut rings = vec![];
+    while let Some((&start, _)) = next.first_key_value() {
+        let mut ring: Vec<(f64, f64)> = vec![];
+        let mut edge = start;
+        while let Some(following) = next.remove(&edge) {
+            let point = field.crossing(edge);
+            ring.push(match ring.last() {
+                Some(&(_, lon)) => (point.0, unwrap(point.1, lon)),
+                None => point,
+            });
+            edge = following;
+        }
+        close(&mut ring);
+        rings.push(ring);
+    }
+    rings
+}
+
+// Longitude `lon` shifted by whole turns to within half a turn of `previous`
+fn unwrap(lon: f64, previous: f64) -> f64 {
+    lon - ((lon - previous) / 360.).round() * 360.
+}
+
+// A ring that goes around the globe has the pole on its left, eastwards the north pole
+fn close(ring: &mut Vec<(f64, f64)>) {
+    let (Some(&first), Some(&last)) = (ring.first(), ring.last()) else {
+        return;
+    };
+    let turn = unwrap(first.1, last.1) - first.1;
+    if turn.abs() > 180. {
+        let pole = 90f64.copysign(turn);
+        ring.push((first.0, first.1 + turn));
+        ring.push((pole, first.1 + turn));
+        ring.push((pole, first.1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LatitudeSpacing;
+
+    fn field(grid: &GlobalGrid, f: impl Fn(f64, f64) -> f64) -> Vec<f64> {
+        grid.positions().map(|(lat, lon)| f(lat, lon)).collect()
+    }
+
+    // Twice the signed area of a ring in degrees², positive counterclockwise
+    fn area(ring: &[(f64, f64)]) -> f64 {
+        (0..ring.len())
+            .map(|k| {
+                let (a, b) = (ring[k], ring[(k + 1) % ring.len()]);
+                a.1 * b.0 - b.1 * a.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn closed_rings() {
+        let grid = GlobalGrid::new(LatitudeSpacing::Equiangular, 37, 72).with_start_lon(-180.);
+        // a disc of radius 10 degrees across the antimeridian
+        let values = field(&grid, |lat, lon| {
+            let dlon = unwrap(lon, 175.) - 175.;
+            (lat - 20.).hypot(dlon)
+        });
+        let rings = contours(&grid, &values, 10.);
+        assert_eq!(rings.len(), 1);
+        for &(lat, lon) in &rings[0] {
+            assert!(((lat - 20.).hypot(unwrap(lon, 175.) - 175.) - 10.).abs() < 0.5);
+        }
+        // counterclockwise around the zone inside, a little smaller than the disc
+        assert!((area(&rings[0]) / 2. - 314.).abs() < 30.);
+    }
+
+    #[test]
+    fn rings_around_poles() {
+        let grid = GlobalGrid::new(LatitudeSpacing::Equiangular, 19, 36);
+        let values = field(&grid, |lat, lon| 90. - lat.abs() + lon.to_radians().cos());
+        let rings = contours(&grid, &values, 20.);
+        assert_eq!(rings.len(), 2);
+        for ring in &rings {
+            let pole = ring.last().unwrap().0;
+            assert_eq!(pole.abs(), 90.);
+            // the cap from the contour to the pole, about 20 degrees of latitude all around
+            assert!((area(ring).abs() / 2. - 360. * 20.).abs() < 200.);
+            assert!(ring[..ring.len() - 2]
+                .iter()
+                .all(|&(lat, _)| (lat.abs() - 70.).abs() < 1.1));
+        }
+        let constant = vec![1.; grid.len()];
+        assert!(contours(&grid, &constant, 2.).is_empty());
+    }
+
+    #[test]
+    fn zones_from_intensity() {
+        assert_eq!(
+            CompassZone::from_horizontal_intensity(1999.),
+            CompassZone::Blackout
+        );
+        assert_eq!(
+            CompassZone::from_horizontal_intensity(f64::NAN),
+            CompassZone::Blackout
+        );
+        assert_eq!(
+            CompassZone::from_horizontal_intensity(2000.),
+            CompassZone::Caution
+        );
+        assert_eq!(
+            CompassZone::from_horizontal_intensity(6000.),
+            CompassZone::Normal
+        );
+    }
+}
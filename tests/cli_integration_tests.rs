@@ -0,0 +1,147 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+use ferromagnetic::{igrf, wmm};
+
+fn ferromagnetic(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ferromagnetic"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_point_csv_matches_library() {
+    let output = ferromagnetic(
+        &["-f", "csv", "-m", "wmm2020", "-33.9", "18.4", "1", "2022.5"],
+        "",
+    );
+    assert!(output.status.success());
+    let out = stdout(&output);
+    let lines = out.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[0],
+        "lat,lon,alt,date,D,I,H,X,Y,Z,F,dD,dI,dH,dX,dY,dZ,dF"
+    );
+    let values = lines[1]
+        .split(',')
+        .map(|v| v.parse::<f64>().unwrap())
+        .collect::<Vec<_>>();
+    let expected = wmm::WMM::default().calc(-33.9, 18.4, 1., 2022.5);
+    assert_eq!(values[4], expected.result.declination);
+    assert_eq!(values[10], expected.result.total_intensity);
+    assert_eq!(values[17], expected.sv.total_intensity);
+}
+
+#[test]
+fn test_column_file_as_json() {
+    let output = ferromagnetic(
+        &["--file", "-", "-f", "json"],
+        "lat;lon;alt;date\n59.9;10.7;0;2020.5\n\n# comment\n0 0 100 2024\n",
+    );
+    assert!(output.status.success());
+    let out = stdout(&output);
+    assert!(out.starts_with("{\"model\":\"IGRF-14\",\"results\":["));
+    assert_eq!(out.matches("\"lat\"").count(), 2);
+    let expected = igrf::IGRF::default().calc(0., 0., 100., 2024.);
+    assert!(out.contains(&format!("\"F\":{}", expected.result.total_intensity)));
+    assert!(out.trim_end().ends_with("]}"));
+}
+
+#[test]
+fn test_geomag70_batch() {
+    let cof = Path::new(env!("CARGO_MANIFEST_DIR")).join("coeffs/cof/IGRF13.COF");
+    let output = ferromagnetic(
+        &["--coeffs", cof.to_str().unwrap(), "--geomag70", "-"],
+        "2015.5 D K0 65 -147.5\n2017,1,1 D M1000 40,30,0 -105,30\n1850 D K0 0 0\n",
+    );
+    // the last line is outside the model
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr.clone()).unwrap();
+    assert!(stderr.contains("line 3"));
+    let out = stdout(&output);
+    let lines = out.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("Date Coord-System Altitude Latitude Longitude D_deg"));
+    assert!(lines[2].starts_with("2017,1,1 D M1000 40,30,0 -105,30 "));
+    let expected = igrf::IGRF::generation(igrf::IgrfGeneration::Igrf13)
+        .calc(65., -147.5, 0., 2015.5)
+        .result;
+    let fields = lines[1].split_whitespace().collect::<Vec<_>>();
+    assert_eq!(fields[5], format!("{}d", expected.declination.trunc()));
+    let f = fields[13].parse::<f64>().unwrap();
+    assert!((f - expected.total_intensity).abs() < 0.1);
+}
+
+#[test]
+fn test_invalid_arguments() {
+    let output = ferromagnetic(&["-m", "IGRF-99", "0", "0", "0", "2020"], "");
+    assert!(!output.status.success());
+    assert!(stdout(&output).is_empty());
+    let output = ferromagnetic(&["0", "0", "0"], "");
+    assert_eq!(output.status.code(), Some(2));
+    let output = ferromagnetic(&["--list-models"], "");
+    assert!(stdout(&output).lines().any(|model| model == "WMM2020"));
+}
+
+#[test]
+fn test_reference_table() {
+    let output = ferromagnetic(
+        &[
+            "table", "-m", "IGRF-13", "--label", "Oslo", "59.9", "10.7", "0", "2000.5", "2003.5",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    let expected = ferromagnetic::ReferenceTable::generate(
+        &igrf::IGRF::generation(igrf::IgrfGeneration::Igrf13),
+        ferromagnetic::CoordinateSystem::Geodetic,
+        59.9,
+        10.7,
+        0.,
+        [2000.5, 2001.5, 2002.5, 2003.5],
+    )
+    .unwrap()
+    .with_label("Oslo");
+    assert_eq!(stdout(&output), expected.to_string());
+    assert!(
+        stdout(&output).starts_with("Lat  59.900 geodetic    Long   10.700     0.000 km Oslo\n")
+    );
+
+    for args in [
+        &["table", "60", "5", "0", "2000", "inf"][..],
+        &["table", "--step", "1e-300", "60", "5", "0", "2000", "2010"],
+        &["table", "--step", "-1", "60", "5", "0", "2000", "2010"],
+        &["table", "--step", "nan", "60", "5", "0", "2000", "2010"],
+    ] {
+        let output = ferromagnetic(args, "");
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert!(stdout(&output).is_empty());
+    }
+}
+
+#[test]
+fn test_iso_8601_date() {
+    let output = ferromagnetic(&["-f", "csv", "59.9", "10.7", "0", "2024-07-02"], "");
+    assert!(output.status.success());
+    let out = stdout(&output);
+    let values = out.lines().nth(1).unwrap().split(',').collect::<Vec<_>>();
+    assert_eq!(values[3], "2024.5");
+    let output = ferromagnetic(&["59.9", "10.7", "0", "2023-02-29"], "");
+    assert!(!output.status.success());
+}
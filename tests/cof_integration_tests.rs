@@ -0,0 +1,91 @@
+use ferromagnetic::cof::CofModel;
+use ferromagnetic::{DateStatus, GeomagError};
+use std::path::Path;
+
+const NT_ABS_TOL: f64 = 1.;
+const DEG_ABS_TOL: f64 = 0.01;
+
+fn read_cof(name: &str) -> CofModel {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("coeffs/cof")
+        .join(name);
+    CofModel::from_cof_reader(std::fs::File::open(path).unwrap()).unwrap()
+}
+
+// The reference tables in testdata/igrf are produced with IGRF-13
+#[test]
+fn test_igrf13_cof_against_testdata() {
+    let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/igrf");
+    let model = read_cof("IGRF13.COF");
+    for x in 1..6 {
+        let contents = std::fs::read_to_string(testdata.join(format!("set{}", x))).unwrap();
+        let hdr = contents
+            .lines()
+            .next()
+            .unwrap()
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        let lat = hdr[1].parse::<f64>().unwrap();
+        let lon = hdr[4].parse::<f64>().unwrap();
+        let alt = hdr[5].parse::<f64>().unwrap();
+        for line in contents.lines().skip(2) {
+            let chunks = line
+                .split_whitespace()
+                .map(|c| c.parse::<f64>().unwrap())
+                .collect::<Vec<_>>();
+            let actual = model.calc(lat, lon, alt, chunks[0]).result;
+            let expected = [
+                (actual.declination, chunks[1], DEG_ABS_TOL),
+                (actual.inclination, chunks[3], DEG_ABS_TOL),
+                (actual.horizontal_intensity, chunks[5], NT_ABS_TOL),
+                (actual.orthogonal_strength.north, chunks[7], NT_ABS_TOL),
+                (actual.orthogonal_strength.east, chunks[9], NT_ABS_TOL),
+                (actual.orthogonal_strength.down, chunks[11], NT_ABS_TOL),
+                (actual.total_intensity, chunks[13], NT_ABS_TOL),
+            ];
+            for (a, b, tol) in expected {
+                assert!(
+                    (a - b).abs() <= tol,
+                    "set{} {}: {} != {}",
+                    x,
+                    chunks[0],
+                    a,
+                    b
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_all_cof_files_parse() {
+    for name in [
+        "IGRF11.COF",
+        "IGRF12.COF",
+        "IGRF13.COF",
+        "IGRF14.COF",
+        "WMM2015.COF",
+    ] {
+        let model = read_cof(name);
+        let (_, max) = model.validity();
+        assert!(model.try_calc(0., 0., 0., max).is_ok(), "{}", name);
+        assert!(matches!(
+            model.try_calc(0., 0., 0., max + 0.1),
+            Err(GeomagError::DateOutOfRange { .. })
+        ));
+    }
+}
+
+#[test]
+fn test_provenance_of_blocks() {
+    let model = read_cof("IGRF13.COF");
+    let provenance = |date: f64| model.calc(45., 0., 0., date).provenance;
+    assert_eq!(provenance(2015.).model, "IGRF2020");
+    assert_eq!(model.try_at(2015.).unwrap().name(), "IGRF2020");
+    assert_eq!(provenance(2015.).date_status, DateStatus::Definitive);
+    assert_eq!(provenance(2015.).epochs, (2015., 2015.));
+    assert_eq!(provenance(2017.).date_status, DateStatus::Interpolated);
+    assert_eq!(provenance(2017.).epochs, (2015., 2020.));
+    assert_eq!(provenance(2022.).date_status, DateStatus::Predicted);
+    assert_eq!(provenance(2022.).epochs, (2020., 2025.));
+}
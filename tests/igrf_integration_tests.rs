@@ -1,4 +1,8 @@
-use ferromagnetic::{igrf, MagneticComponents, OrthogonalStrength};
+use ferromagnetic::{
+    igrf, table, wmm, CoordinateSystem, DateStatus, EccentricDipole, Extrapolation, Generation,
+    GeomagError, MagneticComponents, MltFrame, ModelDate, OrthogonalStrength, PoleTrack,
+    ReferenceTable, TraceDirection, TraceOptions, TraceStop,
+};
 use igrf::IGRFresults;
 use std::path::Path;
 
@@ -12,6 +16,7 @@ fn lines_to_igrf(chunks: &[&str]) -> IGRFresults {
                 north: chunks[7].parse::<f64>().unwrap(),
                 east: chunks[9].parse::<f64>().unwrap(),
                 down: chunks[11].parse::<f64>().unwrap(),
+                ..Default::default()
             },
             total_intensity: chunks[13].parse::<f64>().unwrap(),
         },
@@ -23,9 +28,11 @@ fn lines_to_igrf(chunks: &[&str]) -> IGRFresults {
                 north: chunks[8].parse::<f64>().unwrap(),
                 east: chunks[10].parse::<f64>().unwrap(),
                 down: chunks[12].parse::<f64>().unwrap(),
+                ..Default::default()
             },
             total_intensity: chunks[14].parse::<f64>().unwrap(),
         },
+        ..Default::default()
     }
 }
 
@@ -73,10 +80,9 @@ fn assert_close(a: f64, b: f64, rel_tol: f64, abs_tol: f64) {
     );
 }
 
-#[test]
-fn test_igrf_data() {
+// Compares against the reference tables in testdata/igrf up to the date `until`
+fn assert_matches_testdata(igrf: &igrf::IGRF, until: f64) {
     let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/igrf");
-    let igrf = igrf::IGRF::default();
     for x in 1..11 {
         let contents = std::fs::read_to_string(testdata.join(format!("set{}", x))).unwrap();
         let raw_hdr = contents
@@ -91,6 +97,9 @@ fn test_igrf_data() {
         for line in contents.lines().skip(2) {
             let chunks = line.split_whitespace().collect::<Vec<_>>();
             let date = chunks[0].parse::<f64>().unwrap();
+            if date > until {
+                continue;
+            }
             let expected = lines_to_igrf(&chunks).result;
             let actual = igrf.calc(lat, lon, alt, date).result;
 
@@ -145,3 +154,567 @@ fn test_igrf_data() {
         }
     }
 }
+
+// The reference tables are produced with IGRF-13, which shares the definitive
+// coefficients up to 2015 with the default model
+#[test]
+fn test_igrf_data() {
+    assert_matches_testdata(&igrf::IGRF::default(), 2015.);
+}
+
+#[test]
+fn test_igrf13_data() {
+    let igrf = igrf::IGRF::generation(igrf::IgrfGeneration::Igrf13);
+    assert_matches_testdata(&igrf, f64::INFINITY);
+}
+
+#[test]
+fn test_generations() {
+    use igrf::IgrfGeneration::*;
+    for generation in [Igrf11, Igrf12, Igrf13, Igrf14] {
+        let igrf = igrf::IGRF::generation(generation);
+        assert_eq!(igrf.validity(), generation.validity());
+        let (_, max) = generation.validity();
+        assert!(igrf.try_calc(59.9, 10.7, 0., max).is_ok());
+        assert!(igrf.try_calc(59.9, 10.7, 0., max + 0.5).is_err());
+    }
+    assert_eq!(igrf::IGRF::default().validity(), Igrf14.validity());
+
+    // IGRF-11 and IGRF-14 agree on the definitive 2000 - 2005 interval
+    let old = igrf::IGRF::generation(Igrf11)
+        .calc(59.9, 10.7, 0., 2002.5)
+        .result;
+    let new = igrf::IGRF::default().calc(59.9, 10.7, 0., 2002.5).result;
+    assert!((old.total_intensity - new.total_intensity).abs() < 1e-9);
+}
+
+#[test]
+fn test_igrf13_shc_file() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("coeffs/shc/igrf13coeffs.txt");
+    let igrf = igrf::IGRF::from_shc_reader(std::fs::File::open(path).unwrap()).unwrap();
+    assert_eq!(igrf.validity(), (1900., 2025.));
+    assert_matches_testdata(&igrf, f64::INFINITY);
+}
+
+#[test]
+fn test_try_calc_rejects_invalid_input() {
+    let igrf = igrf::IGRF::default();
+    assert!(igrf.try_calc(59.9, 10.7, 0., 2024.5).is_ok());
+    assert!(igrf.try_calc(59.9, 10.7, 0., 2030.).is_ok());
+    assert_eq!(
+        igrf.try_calc(59.9, 10.7, 0., 2030.5).err(),
+        Some(GeomagError::DateOutOfRange {
+            date: 2030.5,
+            min: 1900.,
+            max: 2030.
+        })
+    );
+    assert_eq!(
+        igrf.try_calc(90.1, 10.7, 0., 2024.5).err(),
+        Some(GeomagError::LatitudeOutOfRange(90.1))
+    );
+    assert_eq!(
+        igrf.try_calc(59.9, -180.5, 0., 2024.5).err(),
+        Some(GeomagError::LongitudeOutOfRange(-180.5))
+    );
+    assert_eq!(
+        igrf.try_calc(f64::NAN, 10.7, 0., 2024.5).err(),
+        Some(GeomagError::NonFinite)
+    );
+    assert!(matches!(
+        igrf.try_calc(59.9, 10.7, -2., 2024.5),
+        Err(GeomagError::AltitudeOutOfRange { .. })
+    ));
+}
+
+#[test]
+fn test_geocentric_coordinates() {
+    let igrf = igrf::IGRF::default();
+    // geodetic 45N, 100 m converted to geocentric latitude and radius
+    let (a, f) = (6378.137, 1. / 298.257223563);
+    let e2 = f * (2. - f);
+    let (lat, h) = (45f64.to_radians(), 0.1);
+    let n = a / (1. - e2 * lat.sin().powi(2)).sqrt();
+    let rho = (n + h) * lat.cos();
+    let z = (n * (1. - e2) + h) * lat.sin();
+    let (gc_lat, r) = (z.atan2(rho).to_degrees(), rho.hypot(z));
+
+    let gd = igrf.try_calc(45., 20., h, 2020.).unwrap().result;
+    let gc = igrf
+        .try_calc_in(CoordinateSystem::Geocentric, gc_lat, 20., r, 2020.)
+        .unwrap()
+        .result;
+    assert!((gd.total_intensity - gc.total_intensity).abs() < 0.01);
+    // the frames differ by the angle between the two latitudes
+    assert!((gd.inclination - gc.inclination).abs() > 0.1);
+
+    let (gd, gc) = (gd.orthogonal_strength, gc.orthogonal_strength);
+    assert!((gd.spherical.radial - gc.spherical.radial).abs() < 0.01);
+    assert!((gd.spherical.theta - gc.spherical.theta).abs() < 0.01);
+    assert!((gd.spherical.phi - gc.spherical.phi).abs() < 0.01);
+    assert_eq!(gc.north, -gc.spherical.theta);
+    assert_eq!(gc.east, gc.spherical.phi);
+    assert_eq!(gc.down, -gc.spherical.radial);
+
+    // radius below the surface is checked as altitude
+    assert!(matches!(
+        igrf.try_calc_in(CoordinateSystem::Geocentric, 0., 20., 6370., 2020.),
+        Err(GeomagError::AltitudeOutOfRange { .. })
+    ));
+}
+
+#[test]
+fn test_gradient_matches_finite_differences() {
+    let igrf = igrf::IGRF::default();
+    let xyz = |coords, lat: f64, lon: f64, h: f64| {
+        let b = igrf
+            .try_calc_in(coords, lat, lon, h, 2022.)
+            .unwrap()
+            .result
+            .orthogonal_strength;
+        [b.north, b.east, b.down]
+    };
+    let central = |f: &dyn Fn(f64) -> [f64; 3], step: f64| {
+        let (a, b) = (f(step), f(-step));
+        [0, 1, 2].map(|i| (a[i] - b[i]) / (2. * step))
+    };
+    for (coords, h) in [
+        (CoordinateSystem::Geodetic, 300.),
+        (CoordinateSystem::Geocentric, 6700.),
+    ] {
+        let (lat, lon) = (-33.5, 151.2);
+        let grad = igrf.try_gradient_in(coords, lat, lon, h, 2022.).unwrap();
+        let t = grad.tensor;
+        // the field is free of divergence and curl
+        assert!((t[0][0] + t[1][1] + t[2][2]).abs() < 1e-9);
+        for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+            assert!((t[i][j] - t[j][i]).abs() < 1e-9);
+        }
+
+        let d_lat = central(&|d| xyz(coords, lat + d, lon, h), 1e-4);
+        let d_lon = central(&|d| xyz(coords, lat, lon + d, h), 1e-4);
+        let d_alt = central(&|d| xyz(coords, lat, lon, h + d), 1e-3);
+        for i in 0..3 {
+            assert!((grad.d_lat[i] - d_lat[i]).abs() < 1e-3);
+            assert!((grad.d_lon[i] - d_lon[i]).abs() < 1e-3);
+            assert!((grad.d_alt[i] - d_alt[i]).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn test_analytic_secular_variation() {
+    let igrf = igrf::IGRF::default();
+    let compat = igrf::IGRF::default().with_sv_mode(igrf::SecularVariation::FiniteDifference);
+    let (lat, lon, alt, date) = (64.1, -21.9, 0., 2022.3);
+
+    let sv = igrf.calc(lat, lon, alt, date).sv;
+    let before = igrf.calc(lat, lon, alt, date - 0.01).result;
+    let after = igrf.calc(lat, lon, alt, date + 0.01).result;
+    let rate = |a: f64, b: f64| (b - a) / 0.02;
+    assert_close(
+        sv.orthogonal_strength.north,
+        rate(
+            before.orthogonal_strength.north,
+            after.orthogonal_strength.north,
+        ),
+        1e-6,
+        1e-6,
+    );
+    assert_close(
+        sv.total_intensity,
+        rate(before.total_intensity, after.total_intensity),
+        1e-4,
+        1e-4,
+    );
+    // minutes per year
+    assert_close(
+        sv.declination,
+        rate(before.declination, after.declination) * 60.,
+        1e-4,
+        1e-4,
+    );
+
+    // the one year difference matches as long as it stays in the same year
+    let fd = compat.calc(lat, lon, alt, date).sv;
+    assert_close(
+        fd.orthogonal_strength.east,
+        sv.orthogonal_strength.east,
+        1e-9,
+        1e-6,
+    );
+    // near an epoch it keeps extrapolating the interval the date is in
+    let fd = compat.calc(lat, lon, alt, 2024.5).sv;
+    let before = igrf.calc(lat, lon, alt, 2024.5).sv;
+    let after = igrf.calc(lat, lon, alt, 2025.).sv;
+    assert_close(
+        fd.orthogonal_strength.down,
+        before.orthogonal_strength.down,
+        0.,
+        0.1,
+    );
+    assert!((fd.orthogonal_strength.down - after.orthogonal_strength.down).abs() > 1.);
+}
+
+#[test]
+fn test_model_dates() {
+    let igrf = igrf::IGRF::default();
+    let date = "2021-07-02T12:00:00Z".parse::<ModelDate>().unwrap();
+    assert_eq!(date.decimal_year(), 2021.5);
+    assert_eq!(
+        igrf.calc(-33.9, 18.4, 0., date),
+        igrf.calc(-33.9, 18.4, 0., 2021.5)
+    );
+    // every way of giving a date interpolates the same
+    let dates = [
+        ModelDate::from_ymd_hms(2022, 3, 1, 6, 0, 0.).unwrap(),
+        ModelDate::from_day_of_year(2022, 60.25).unwrap(),
+        ModelDate::from_unix(1646114400.),
+        ModelDate::from_julian_date(2459639.75),
+    ];
+    let expected = igrf.calc(-33.9, 18.4, 0., dates[0]).result.total_intensity;
+    for date in dates {
+        let f = igrf.calc(-33.9, 18.4, 0., date).result.total_intensity;
+        assert!((f - expected).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_extrapolation() {
+    let (lat, lon, alt) = (-33.9, 18.4, 0.);
+    let igrf = igrf::IGRF::default();
+    assert!(matches!(
+        igrf.try_calc(lat, lon, alt, 2031.),
+        Err(GeomagError::DateOutOfRange { .. })
+    ));
+    let edge = igrf.calc(lat, lon, alt, 2030.);
+    assert!(!edge.extrapolated());
+
+    let clamped = igrf::IGRF::default()
+        .with_extrapolation(Extrapolation::Clamp)
+        .calc(lat, lon, alt, 2032.);
+    assert!(clamped.extrapolated());
+    assert_eq!(clamped.result, edge.result);
+    assert_eq!(clamped.sv, edge.sv);
+
+    let linear = igrf::IGRF::default().with_extrapolation(Extrapolation::Linear { max_years: 5. });
+    let field = linear.calc(lat, lon, alt, 2032.);
+    assert!(field.extrapolated());
+    assert!(!linear.calc(lat, lon, alt, 2029.).extrapolated());
+    let (a, b) = (
+        &field.result.orthogonal_strength,
+        &edge.result.orthogonal_strength,
+    );
+    let sv = &edge.sv.orthogonal_strength;
+    assert_close(a.north, b.north + 2. * sv.north, 1e-12, 1e-9);
+    assert_close(a.down, b.down + 2. * sv.down, 1e-12, 1e-9);
+    assert_eq!(field.sv.orthogonal_strength, edge.sv.orthogonal_strength);
+    assert_eq!(
+        linear.try_calc(lat, lon, alt, 2035.5),
+        Err(GeomagError::DateOutOfRange {
+            date: 2035.5,
+            min: 1895.,
+            max: 2035.
+        })
+    );
+    let before = linear.calc(lat, lon, alt, 1898.);
+    let first = linear.calc(lat, lon, alt, 1900.);
+    let (a, b) = (
+        &before.result.orthogonal_strength,
+        &first.result.orthogonal_strength,
+    );
+    assert_close(
+        a.east,
+        b.east - 2. * first.sv.orthogonal_strength.east,
+        1e-12,
+        1e-9,
+    );
+
+    // the difference over a year keeps extrapolating a year ahead
+    let compat = igrf::IGRF::default()
+        .with_sv_mode(igrf::SecularVariation::FiniteDifference)
+        .with_extrapolation(Extrapolation::Linear { max_years: 5. });
+    let edge = compat.calc(lat, lon, alt, 2030.);
+    let field = compat.calc(lat, lon, alt, 2031.);
+    let (a, b) = (
+        &field.result.orthogonal_strength,
+        &edge.result.orthogonal_strength,
+    );
+    assert_close(
+        a.north,
+        b.north + edge.sv.orthogonal_strength.north,
+        1e-12,
+        1e-9,
+    );
+    assert_close(
+        field.sv.orthogonal_strength.north,
+        edge.sv.orthogonal_strength.north,
+        1e-9,
+        1e-9,
+    );
+}
+
+#[test]
+fn test_provenance() {
+    let igrf = igrf::IGRF::default();
+    let provenance =
+        |model: &igrf::IGRF, lat: f64, date: f64| model.calc(lat, 0., 0., date).provenance;
+
+    let definitive = provenance(&igrf, 45., 2020.);
+    assert_eq!(definitive.model, "IGRF-14");
+    assert_eq!(igrf.try_at(2020.).unwrap().name(), "IGRF-14");
+    assert_eq!(
+        definitive.generation,
+        Generation::Igrf(igrf::IgrfGeneration::Igrf14)
+    );
+    assert_eq!(definitive.date_status, DateStatus::Definitive);
+    assert_eq!(definitive.epochs, (2020., 2020.));
+    assert_eq!(definitive.nmax, 13);
+    assert!(!definitive.near_pole);
+
+    let interpolated = provenance(&igrf, 45., 1952.5);
+    assert_eq!(interpolated.date_status, DateStatus::Interpolated);
+    assert_eq!(interpolated.epochs, (1950., 1955.));
+    assert_eq!(interpolated.nmax, 10);
+    // the IGRF epochs before 1945 were never made definitive
+    assert_eq!(
+        provenance(&igrf, 45., 1900.).date_status,
+        DateStatus::Interpolated
+    );
+    let predicted = provenance(&igrf, 45., 2027.);
+    assert_eq!(predicted.date_status, DateStatus::Predicted);
+    assert_eq!(predicted.epochs, (2025., 2030.));
+
+    let linear = igrf::IGRF::default().with_extrapolation(Extrapolation::Linear { max_years: 5. });
+    let extrapolated = provenance(&linear, 45., 2031.);
+    assert_eq!(extrapolated.date_status, DateStatus::Extrapolated);
+    assert_eq!(extrapolated.epochs, (2025., 2030.));
+
+    // declination is undefined where the latitude is moved off the pole
+    let pole = igrf.calc(90., 0., 0., 2020.);
+    assert!(pole.provenance.near_pole);
+    assert!(!provenance(&igrf, -89.99, 2020.).near_pole);
+    assert!(provenance(&igrf, -89.9995, 2020.).near_pole);
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("coeffs/shc_nosv/IGRF14.shc");
+    let shc = igrf::IGRF::from_shc_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let custom = provenance(&shc, 45., 2020.);
+    assert_eq!(custom.generation, Generation::Custom);
+    assert_eq!(custom.model, "SHC");
+    assert_eq!(shc.try_at(2020.).unwrap().name(), "SHC");
+    assert_eq!(custom.date_status, DateStatus::Interpolated);
+    let renamed = shc.with_name("Custom").calc(45., 0., 0., 2020.).provenance;
+    assert_eq!(renamed.model, "Custom");
+
+    let wmm = wmm::WMM::default().calc(45., 0., 0., 2022.).provenance;
+    assert_eq!(wmm.model, "WMM2020");
+    assert_eq!(wmm.generation, Generation::Wmm(wmm::WmmVersion::Wmm2020));
+    assert_eq!(wmm.date_status, DateStatus::Predicted);
+    assert_eq!((wmm.epochs, wmm.nmax), ((2020., 2025.), 12));
+}
+
+#[test]
+fn test_dipole() {
+    // DGRF 2020 puts the geomagnetic north pole at 80.59N 72.68W with a dipole moment of
+    // 7.71e22 A m²
+    let dipole = igrf::IGRF::default().at(2020.).dipole();
+    let (lat, lon) = dipole.north_pole();
+    assert_close(lat, 80.59, 0., 0.01);
+    assert_close(lon, -72.68, 0., 0.01);
+    assert_close(dipole.moment(), 7.71e22, 1e-3, 0.);
+    assert_close(dipole.tilt(), 90. - lat, 1e-12, 1e-12);
+    let (lat, lon) = dipole.south_pole();
+    assert_close(lat, -80.59, 0., 0.01);
+    assert_close(lon, 107.32, 0., 0.01);
+
+    // the pole drifts with the date
+    let (lat_1900, _) = igrf::IGRF::default().at(1900.).dipole().north_pole();
+    assert_close(lat_1900, 78.61, 0., 0.01);
+    let (mlat, mlon) = dipole.to_geomagnetic(59.9, 10.7);
+    assert_close(mlat, 59.65, 0., 0.01);
+    let (lat, lon) = dipole.from_geomagnetic(mlat, mlon);
+    assert_close(lat, 59.9, 1e-12, 1e-9);
+    assert_close(lon, 10.7, 1e-12, 1e-9);
+}
+
+#[test]
+fn test_eccentric_dipole() {
+    // in 2020 the eccentric dipole is about 590 km off centre towards the western Pacific
+    let snapshot = igrf::IGRF::default().at(2020.);
+    let eccentric = snapshot.eccentric_dipole();
+    let (lat, lon, distance) = eccentric.offset();
+    assert_close(lat, 22.66, 0., 0.01);
+    assert_close(lon, 136.97, 0., 0.01);
+    assert_close(distance, 590.5, 0., 0.1);
+    assert_eq!(eccentric.dipole(), snapshot.dipole());
+    let (lat, lon) = eccentric.north_pole();
+    assert_close(lat, 84.56, 0., 0.01);
+    assert_close(lon, -99.20, 0., 0.01);
+
+    // a better fit to the field than the centred dipole
+    let (g10, g11, h11) = snapshot.dipole().coefficients();
+    let centred = EccentricDipole::new([g10, g11, h11, 0., 0., 0., 0., 0.]);
+    let misfit = |dipole: &EccentricDipole| {
+        let mut sum = 0.;
+        for lat in (-80..=80).step_by(20) {
+            for lon in (-180..180).step_by(20) {
+                let (lat, lon) = (lat as f64, lon as f64);
+                let a = snapshot.calc(lat, lon, 0.).result.orthogonal_strength;
+                let b = dipole.calc(lat, lon, 0.).orthogonal_strength;
+                sum += (a.north - b.north).powi(2)
+                    + (a.east - b.east).powi(2)
+                    + (a.down - b.down).powi(2);
+            }
+        }
+        sum
+    };
+    assert!(misfit(&eccentric) < 0.6 * misfit(&centred));
+}
+
+#[test]
+fn test_magnetic_local_time() {
+    // magnetic noon in Tromsø is around 09:30 UTC
+    let snapshot = igrf::IGRF::default().at(2020.67);
+    let noon = ModelDate::from_ymd_hms(2020, 9, 1, 9, 30, 0.).unwrap();
+    let geodetic = CoordinateSystem::Geodetic;
+    let mlt = |frame, instant| {
+        snapshot
+            .try_mlt(frame, geodetic, 69.65, 18.96, 0., instant)
+            .unwrap()
+    };
+    let dipole = mlt(MltFrame::Dipole, noon);
+    assert_close(dipole, 12., 0., 0.5);
+    // the offset of the eccentric dipole turns longitudes this close to the pole
+    assert_close(mlt(MltFrame::Eccentric, noon), dipole, 0., 1.);
+    // magnetic time runs with UTC, unevenly as the sun moves around the tilted dipole
+    let later = ModelDate::from_ymd_hms(2020, 9, 1, 15, 30, 0.).unwrap();
+    assert_close(mlt(MltFrame::Dipole, later), dipole + 6., 0., 0.05);
+    assert!(snapshot
+        .try_mlt(MltFrame::Dipole, geodetic, 91., 0., 0., noon)
+        .is_err());
+}
+
+#[test]
+fn test_field_line_tracing() {
+    let snapshot = igrf::IGRF::default().at(2020.);
+    // from central Europe against the field over the equator to the conjugate point
+    let options = TraceOptions::default()
+        .with_direction(TraceDirection::Against)
+        .with_stop_altitude(100.);
+    let line = snapshot
+        .try_trace(&options, CoordinateSystem::Geodetic, 50., 10., 100.)
+        .unwrap();
+    assert_eq!(line.stop, TraceStop::Altitude);
+    let &(lat, lon, alt) = line.points.last().unwrap();
+    assert!(lat < -30. && lat > -60.);
+    assert_close(alt, 100., 0., 1e-5);
+    // the highest point is over the magnetic equator
+    let top = line.points.iter().map(|p| p.2).fold(0., f64::max);
+    assert!(top > 5000.);
+    // and back along the field
+    let back = snapshot
+        .try_trace(
+            &options.with_direction(TraceDirection::Along),
+            CoordinateSystem::Geodetic,
+            lat,
+            lon,
+            alt,
+        )
+        .unwrap();
+    let &(lat, lon, _) = back.points.last().unwrap();
+    assert_close(lat, 50., 0., 1e-4);
+    assert_close(lon, 10., 0., 1e-4);
+    assert_close(back.length, line.length, 1e-6, 0.);
+
+    let equator = snapshot
+        .try_trace(
+            &options
+                .with_stop_at_equator(true)
+                .with_output(CoordinateSystem::Geocentric),
+            CoordinateSystem::Geodetic,
+            50.,
+            10.,
+            100.,
+        )
+        .unwrap();
+    assert_eq!(equator.stop, TraceStop::Equator);
+    assert_eq!(equator.coords, CoordinateSystem::Geocentric);
+    let strength = |&(lat, lon, r): &(f64, f64, f64)| {
+        snapshot
+            .try_calc_in(CoordinateSystem::Geocentric, lat, lon, r)
+            .unwrap()
+            .result
+    };
+    // |B| is smallest at the end, where the field is close to horizontal
+    let end = strength(equator.points.last().unwrap());
+    assert!(end.inclination.abs() < 5.);
+    for point in &equator.points[..equator.points.len() - 1] {
+        assert!(strength(point).total_intensity > end.total_intensity);
+    }
+
+    // WMM is only defined up to 850 km
+    let wmm = wmm::WMM::default().at(2022.5);
+    let line = wmm
+        .try_trace(&options, CoordinateSystem::Geodetic, 50., 10., 100.)
+        .unwrap();
+    assert_eq!(line.stop, TraceStop::AltitudeRange);
+    assert!(line.points.last().unwrap().2 <= 850.);
+}
+
+#[test]
+fn test_pole_track() {
+    let igrf = igrf::IGRF::default();
+    let track = PoleTrack::generate(&igrf, 0., table::yearly(2000., 2020., 10.)).unwrap();
+    let north = track
+        .rows
+        .iter()
+        .map(|(_, poles)| poles.north)
+        .collect::<Vec<_>>();
+    // the north dip pole has moved from the Canadian Arctic across the date line
+    assert_close(north[0].0, 80.97, 0., 0.01);
+    assert_close(north[0].1, -109.64, 0., 0.01);
+    assert!(north[1].0 > north[0].0 && north[1].1 < north[0].1);
+    assert!(north[2].0 > 86. && north[2].1 > 0.);
+    let text = track.to_string();
+    let lines = text.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[0], "Dip poles at 0.000 km");
+    assert_eq!(lines[2], " 2000.0     80.972 -109.640    -64.662  138.303");
+}
+
+#[test]
+fn test_reference_tables_regenerate() {
+    let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/igrf");
+    let igrf = igrf::IGRF::generation(igrf::IgrfGeneration::Igrf13);
+    for x in 1..11 {
+        let contents = std::fs::read_to_string(testdata.join(format!("set{}", x))).unwrap();
+        let header = contents.lines().next().unwrap();
+        let fields = header.split_whitespace().collect::<Vec<_>>();
+        let (lat, lon, alt) = (
+            fields[1].parse::<f64>().unwrap(),
+            fields[4].parse::<f64>().unwrap(),
+            fields[5].parse::<f64>().unwrap(),
+        );
+        let label = header["Lat  59.900 geodetic    Long -109.900     1.100 km ".len()..].trim();
+        let dates = table::yearly(1900.5, 2024.5, 1.);
+        let generated =
+            ReferenceTable::generate(&igrf, CoordinateSystem::Geodetic, lat, lon, alt, dates)
+                .unwrap()
+                .with_label(label)
+                .to_string();
+        assert_eq!(generated.lines().count(), contents.lines().count());
+        for (i, (a, b)) in generated.lines().zip(contents.lines()).enumerate() {
+            if i < 2 {
+                assert_eq!(a, b);
+                continue;
+            }
+            assert_eq!(a.len(), b.len(), "{}\n{}", a, b);
+            // the tables were computed in single precision, allow one in the last digit
+            for (k, (a, b)) in a.split_whitespace().zip(b.split_whitespace()).enumerate() {
+                let unit = if k == 1 || k == 3 { 0.01 } else { 1. };
+                let diff = (a.parse::<f64>().unwrap() - b.parse::<f64>().unwrap()).abs();
+                assert!(diff <= unit * 1.001, "set{} line {}: {} {}", x, i + 1, a, b);
+            }
+        }
+    }
+}
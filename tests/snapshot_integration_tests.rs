@@ -0,0 +1,340 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use ferromagnetic::{
+    igrf, wmm, CoordinateSystem, FieldGradient, FieldResults, Generation, GeomagError,
+    GeomagneticModel, GlobalGrid, Grid, GridAxis, LatitudeSpacing, ModelSnapshot, Registry,
+};
+
+// Counts the allocations made by the current thread
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|a| a.get())
+}
+
+#[test]
+fn test_snapshot_matches_calc() {
+    let models: [Box<dyn GeomagneticModel>; 3] = [
+        Box::new(igrf::IGRF::default()),
+        Box::new(igrf::IGRF::default().with_sv_mode(igrf::SecularVariation::FiniteDifference)),
+        Box::new(wmm::WMM::default()),
+    ];
+    for model in &models {
+        let snapshot = model.try_at(2023.7).unwrap();
+        assert_eq!(snapshot.date(), 2023.7);
+        for (lat, lon, alt) in [(59.9, 10.7, 0.), (-77.8, 166.7, 2.), (0., -70., 400.)] {
+            let a = snapshot.try_calc(lat, lon, alt).unwrap();
+            let b = model.try_calc(lat, lon, alt, 2023.7).unwrap();
+            assert_eq!(a.result.total_intensity, b.result.total_intensity);
+            assert_eq!(a.result.declination, b.result.declination);
+            assert_eq!(a.sv.inclination, b.sv.inclination);
+        }
+    }
+}
+
+#[test]
+fn test_snapshot_does_not_allocate() {
+    let snapshot = igrf::IGRF::default().at(2024.2);
+    let before = allocations();
+    let mut total = 0.;
+    for i in 0..100 {
+        let lat = -89. + 1.78 * i as f64;
+        let field = snapshot.try_calc(lat, 3.6 * i as f64, 10.).unwrap();
+        let gradient = snapshot
+            .try_gradient_in(CoordinateSystem::Geodetic, lat, 0., 10.)
+            .unwrap();
+        total += field.result.total_intensity + gradient.tensor[2][2];
+    }
+    assert_eq!(allocations(), before);
+    assert!(total > 0.);
+}
+
+#[test]
+fn test_snapshot_rejects_invalid_input() {
+    let igrf = igrf::IGRF::default();
+    assert!(matches!(
+        igrf.try_at(2031.),
+        Err(GeomagError::DateOutOfRange { .. })
+    ));
+    assert_eq!(igrf.try_at(f64::NAN).err(), Some(GeomagError::NonFinite));
+    assert_eq!(
+        igrf.at(2020.).try_calc(91., 0., 0.).err(),
+        Some(GeomagError::LatitudeOutOfRange(91.))
+    );
+}
+
+#[test]
+fn test_magnetic_circle() {
+    let models: [Box<dyn GeomagneticModel>; 2] = [
+        Box::new(igrf::IGRF::default().with_sv_mode(igrf::SecularVariation::FiniteDifference)),
+        Box::new(wmm::WMM::default()),
+    ];
+    for model in &models {
+        let snapshot = model.try_at(2022.3).unwrap();
+        for (coords, lat, height) in [
+            (CoordinateSystem::Geodetic, 63.4, 0.5),
+            (CoordinateSystem::Geodetic, -90., 300.),
+            (CoordinateSystem::Geocentric, -12.1, 6400.),
+        ] {
+            let circle = snapshot.try_circle(coords, lat, height).unwrap();
+            assert_eq!((circle.lat(), circle.height()), (lat, height));
+            let before = allocations();
+            for i in 0..=72 {
+                let lon = -180. + 5. * i as f64;
+                assert_eq!(
+                    circle.try_calc(lon).unwrap(),
+                    snapshot.try_calc_in(coords, lat, lon, height).unwrap()
+                );
+            }
+            assert_eq!(allocations(), before);
+        }
+    }
+    let snapshot = igrf::IGRF::default().at(2020.);
+    assert_eq!(
+        snapshot
+            .try_circle(CoordinateSystem::Geodetic, 91., 0.)
+            .err(),
+        Some(GeomagError::LatitudeOutOfRange(91.))
+    );
+    let circle = snapshot
+        .try_circle(CoordinateSystem::Geodetic, 10., 0.)
+        .unwrap();
+    assert_eq!(
+        circle.try_calc(f64::NAN).err(),
+        Some(GeomagError::NonFinite)
+    );
+}
+
+#[test]
+fn test_global_grid_synthesis() {
+    let models: [Box<dyn GeomagneticModel>; 2] = [
+        Box::new(igrf::IGRF::default()),
+        Box::new(igrf::IGRF::default().with_sv_mode(igrf::SecularVariation::FiniteDifference)),
+    ];
+    for model in &models {
+        let snapshot = model.try_at(2019.4).unwrap();
+        for (spacing, nlat, nlon, start) in [
+            (LatitudeSpacing::Equiangular, 19, 36, -175.),
+            (LatitudeSpacing::GaussLegendre, 16, 25, 7.5),
+            // fewer longitudes than orders, which alias onto each other
+            (LatitudeSpacing::GaussLegendre, 4, 6, 0.),
+        ] {
+            let grid = GlobalGrid::new(spacing, nlat, nlon).with_start_lon(start);
+            let mut out = vec![FieldResults::default(); grid.len()];
+            snapshot
+                .try_calc_global(CoordinateSystem::Geodetic, &grid, 120., &mut out)
+                .unwrap();
+            for ((lat, lon), global) in grid.positions().zip(&out) {
+                // the same longitude in radians with geomag70's truncated conversion
+                let lon = lon * std::f64::consts::PI / 180. / 0.01745329;
+                let point = snapshot.calc(lat, lon, 120.);
+                for (a, b) in [(global.result, point.result), (global.sv, point.sv)] {
+                    let (a, b) = (a.orthogonal_strength, b.orthogonal_strength);
+                    assert!((a.north - b.north).abs() < 1e-7);
+                    assert!((a.east - b.east).abs() < 1e-7);
+                    assert!((a.down - b.down).abs() < 1e-7);
+                }
+            }
+        }
+    }
+    let snapshot = wmm::WMM::default().at(2021.);
+    let grid = GlobalGrid::new(LatitudeSpacing::Equiangular, 3, 4);
+    assert!(matches!(
+        snapshot.try_calc_global(
+            CoordinateSystem::Geodetic,
+            &grid,
+            -20.,
+            &mut [FieldResults::default(); 12]
+        ),
+        Err(GeomagError::AltitudeOutOfRange { .. })
+    ));
+    assert!(matches!(
+        snapshot.try_calc_global(
+            CoordinateSystem::Geodetic,
+            &grid,
+            0.,
+            &mut [FieldResults::default(); 11]
+        ),
+        Err(GeomagError::OutputTooSmall {
+            len: 11,
+            needed: 12
+        })
+    ));
+}
+
+#[test]
+fn test_batch_evaluation() {
+    let snapshot = wmm::WMM::default().at(2021.);
+    let grid = Grid {
+        lat: GridAxis::range(-80., 80., 20.),
+        lon: GridAxis::range(-180., 180., 45.),
+        alt: GridAxis::range(0., 400., 200.),
+    };
+    let mut out = vec![FieldResults::default(); grid.len()];
+    snapshot
+        .try_calc_grid(CoordinateSystem::Geodetic, &grid, &mut out)
+        .unwrap();
+    let positions = grid.positions().collect::<Vec<_>>();
+    for (&(lat, lon, alt), result) in positions.iter().zip(&out) {
+        assert_eq!(*result, snapshot.calc(lat, lon, alt));
+    }
+
+    let mut from_slice = vec![FieldResults::default(); grid.len()];
+    snapshot
+        .try_calc_slice(CoordinateSystem::Geodetic, &positions, &mut from_slice)
+        .unwrap();
+    assert_eq!(from_slice, out);
+
+    let mut from_iter = vec![FieldResults::default(); grid.len() + 1];
+    let written = snapshot
+        .try_calc_iter(CoordinateSystem::Geodetic, grid.positions(), &mut from_iter)
+        .unwrap();
+    assert_eq!(written, grid.len());
+    assert_eq!(&from_iter[..written], &out[..]);
+
+    assert_eq!(
+        snapshot
+            .try_calc_iter(CoordinateSystem::Geodetic, grid.positions(), &mut out[..10])
+            .err(),
+        Some(GeomagError::OutputTooSmall {
+            len: 10,
+            needed: grid.len()
+        })
+    );
+    assert!(matches!(
+        snapshot.try_calc_slice(CoordinateSystem::Geodetic, &[(0., 0., 900.)], &mut out),
+        Err(GeomagError::AltitudeOutOfRange { .. })
+    ));
+}
+
+// A model implemented outside the crate, an axial dipole referred to the core-mantle
+// boundary decaying by 10 nT a year
+struct CoreDipole;
+
+impl GeomagneticModel for CoreDipole {
+    fn name(&self) -> &str {
+        "Core"
+    }
+
+    fn validity(&self) -> (f64, f64) {
+        (2000., 2030.)
+    }
+
+    fn altitude_range(&self) -> (f64, f64) {
+        (-3000., 1000.)
+    }
+
+    fn nmax(&self) -> usize {
+        1
+    }
+
+    fn reference_radius(&self) -> f64 {
+        3485.
+    }
+
+    fn try_at(&self, date: f64) -> Result<ModelSnapshot, GeomagError> {
+        let g10 = -200. + 10. * (date - 2000.);
+        ModelSnapshot::from_coefficients(
+            date,
+            self.nmax(),
+            self.reference_radius(),
+            self.altitude_range(),
+            vec![g10, 0., 0.],
+            vec![10., 0., 0.],
+        )
+        .map(|snapshot| snapshot.with_name(self.name()))
+    }
+
+    fn try_calc_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: f64,
+    ) -> Result<FieldResults, GeomagError> {
+        self.try_at(date)?.try_calc_in(coords, lat, lon, height)
+    }
+
+    fn try_gradient_in(
+        &self,
+        coords: CoordinateSystem,
+        lat: f64,
+        lon: f64,
+        height: f64,
+        date: f64,
+    ) -> Result<FieldGradient, GeomagError> {
+        self.try_at(date)?.try_gradient_in(coords, lat, lon, height)
+    }
+}
+
+#[test]
+fn test_model_outside_the_crate() {
+    let mut registry = Registry::empty();
+    registry.register(Box::new(CoreDipole));
+    let core = registry.get("Core").unwrap();
+    let results = core
+        .try_calc_in(CoordinateSystem::Geocentric, 0., 30., 3485., 2010.)
+        .unwrap();
+    // on the equator at the reference radius the field is -g10 northwards
+    assert!((results.result.orthogonal_strength.north - 100.).abs() < 1e-9);
+    assert!(results.result.orthogonal_strength.down.abs() < 1e-9);
+    assert!((results.sv.orthogonal_strength.north + 10.).abs() < 1e-9);
+    assert_eq!(results.provenance.model, "Core");
+    assert_eq!(results.provenance.generation, Generation::Custom);
+    let snapshot = core.try_at(2010.).unwrap();
+    assert_eq!(snapshot.name(), "Core");
+    assert_eq!(snapshot.reference_radius(), 3485.);
+    assert!(matches!(
+        snapshot.try_calc(0., 0., 2000.),
+        Err(GeomagError::AltitudeOutOfRange { .. })
+    ));
+}
+
+#[test]
+fn test_from_coefficients_rejects_invalid_input() {
+    let snapshot = |nmax, radius, range, coeffs: Vec<f64>| {
+        let sv = vec![0.; coeffs.len()];
+        ModelSnapshot::from_coefficients(2020., nmax, radius, range, coeffs, sv).err()
+    };
+    let range = (-1., 100.);
+    assert!(snapshot(1, 6371.2, range, vec![-30000., 0., 0.]).is_none());
+    for invalid in [
+        snapshot(0, 6371.2, range, vec![]),
+        snapshot(2, 6371.2, range, vec![-30000., 0., 0.]),
+        snapshot(usize::MAX, 6371.2, range, vec![-30000., 0., 0.]),
+        snapshot(1, 0., range, vec![-30000., 0., 0.]),
+        snapshot(1, 6371.2, (100., -1.), vec![-30000., 0., 0.]),
+    ] {
+        assert!(matches!(invalid, Some(GeomagError::InvalidModel(_))));
+    }
+    assert_eq!(
+        snapshot(1, 6371.2, range, vec![f64::NAN, 0., 0.]),
+        Some(GeomagError::NonFinite)
+    );
+    assert_eq!(
+        ModelSnapshot::from_coefficients(2020., 1, 6371.2, range, vec![0.; 3], vec![0.; 2]).err(),
+        Some(GeomagError::InvalidModel(
+            "degree 1 needs 3 coefficients, found 3 and 2 annual changes".to_string()
+        ))
+    );
+}
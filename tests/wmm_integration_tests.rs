@@ -0,0 +1,163 @@
+use ferromagnetic::wmm::{WmmVersion, WMM};
+use ferromagnetic::{CompassZone, Extrapolation, PoleTrack};
+
+const NT_ABS_TOL: f64 = 0.1;
+const DEG_ABS_TOL: f64 = 0.01;
+
+// Date, height above ellipsoid (km), lat, lon, X, Y, Z, H, F, I, D
+// Taken from the WMM2020 test values published with the model
+#[rustfmt::skip]
+const WMM2020_TEST_VALUES: [[f64; 11]; 8] = [
+    [2020.0, 0., 80., 0., 6570.4, -146.3, 54606.0, 6572.0, 55000.1, 83.14, -1.28],
+    [2020.0, 0., 0., 120., 39624.3, 109.9, -10932.5, 39624.4, 41104.9, -15.42, 0.16],
+    [2020.0, 0., -80., 240., 5940.6, 15772.1, -52480.8, 16853.8, 55120.6, -72.20, 69.36],
+    [2020.0, 100., 80., 0., 6261.8, -185.5, 52429.1, 6264.5, 52802.0, 83.19, -1.70],
+    [2020.0, 100., 0., 120., 37636.7, 104.9, -10474.8, 37636.9, 39067.3, -15.55, 0.16],
+    [2020.0, 100., -80., 240., 5744.9, 14799.5, -49969.4, 15875.4, 52430.6, -72.37, 68.78],
+    [2022.5, 0., 80., 0., 6529.9, 1.1, 54713.4, 6529.9, 55101.7, 83.19, 0.01],
+    [2022.5, 0., 0., 120., 39684.7, -42.2, -10809.5, 39684.7, 41130.5, -15.24, -0.06],
+];
+
+// Height above ellipsoid (km), lat, lon, Xdot, Ydot, Zdot, Hdot, Fdot, Idot, Ddot (degrees/year)
+#[rustfmt::skip]
+const WMM2020_SV_TEST_VALUES: [[f64; 10]; 2] = [
+    [0., 80., 0., -16.2, 59.0, 42.9, -17.5, 40.5, 0.02, 0.51],
+    [100., 80., 0., -15.1, 56.4, 39.2, -16.8, 36.9, 0.02, 0.51],
+];
+
+fn assert_close(actual: f64, expected: f64, abs_tol: f64) {
+    assert!(
+        (actual - expected).abs() <= abs_tol,
+        "actual={} expected={} abs_tol={}",
+        actual,
+        expected,
+        abs_tol
+    );
+}
+
+#[test]
+fn test_wmm2020_values() {
+    let wmm = WMM::version(WmmVersion::Wmm2020);
+    for [date, alt, lat, lon, x, y, z, h, f, i, d] in WMM2020_TEST_VALUES {
+        let actual = wmm.calc(lat, lon, alt, date).result;
+        assert_close(actual.orthogonal_strength.north, x, NT_ABS_TOL);
+        assert_close(actual.orthogonal_strength.east, y, NT_ABS_TOL);
+        assert_close(actual.orthogonal_strength.down, z, NT_ABS_TOL);
+        assert_close(actual.horizontal_intensity, h, NT_ABS_TOL);
+        assert_close(actual.total_intensity, f, NT_ABS_TOL);
+        assert_close(actual.inclination, i, DEG_ABS_TOL);
+        assert_close(actual.declination, d, DEG_ABS_TOL);
+    }
+}
+
+#[test]
+fn test_wmm2020_sv_values() {
+    let wmm = WMM::version(WmmVersion::Wmm2020);
+    for [alt, lat, lon, xdot, ydot, zdot, hdot, fdot, idot, ddot] in WMM2020_SV_TEST_VALUES {
+        let sv = wmm.calc(lat, lon, alt, 2020.0).sv;
+        assert_close(sv.orthogonal_strength.north, xdot, NT_ABS_TOL);
+        assert_close(sv.orthogonal_strength.east, ydot, NT_ABS_TOL);
+        assert_close(sv.orthogonal_strength.down, zdot, NT_ABS_TOL);
+        assert_close(sv.horizontal_intensity, hdot, NT_ABS_TOL);
+        assert_close(sv.total_intensity, fdot, NT_ABS_TOL);
+        // sv is given in minutes per year
+        assert_close(sv.inclination / 60., idot, DEG_ABS_TOL);
+        assert_close(sv.declination / 60., ddot, DEG_ABS_TOL);
+    }
+}
+
+#[test]
+fn test_all_versions_evaluate_within_validity() {
+    for version in [
+        WmmVersion::Wmm2010,
+        WmmVersion::Wmm2015,
+        WmmVersion::Wmm2015v2,
+        WmmVersion::Wmm2020,
+    ] {
+        let wmm = WMM::version(version);
+        let result = wmm.calc(59.9, 10.7, 0., wmm.epoch() + 2.5).result;
+        assert!(result.total_intensity > 45000. && result.total_intensity < 55000.);
+    }
+}
+
+#[test]
+fn test_extrapolation_continues_secular_variation() {
+    let wmm = WMM::default().with_extrapolation(Extrapolation::Linear { max_years: 1. });
+    assert!(WMM::default().try_calc(40., -105., 0., 2025.5).is_err());
+    let edge = wmm.calc(40., -105., 0., 2025.);
+    let field = wmm.calc(40., -105., 0., 2025.5);
+    assert!(field.extrapolated() && !edge.extrapolated());
+    let north = edge.result.orthogonal_strength.north + 0.5 * edge.sv.orthogonal_strength.north;
+    assert_close(field.result.orthogonal_strength.north, north, 1e-9);
+    assert!(wmm.try_calc(40., -105., 0., 2026.5).is_err());
+}
+
+#[test]
+fn test_compass_zones() {
+    let wmm = WMM::default();
+    assert_eq!(
+        wmm.calc(59.9, 10.7, 0., 2022.5).compass_zone(),
+        CompassZone::Normal
+    );
+    let snapshot = wmm.at(2022.5);
+    let zones = snapshot.try_zone_boundaries(0., 181).unwrap();
+    for (rings, level) in [(&zones.blackout, 2000.), (&zones.caution, 6000.)] {
+        // one zone around each magnetic pole
+        assert!(rings.iter().any(|ring| ring[0].0 > 60.));
+        assert!(rings.iter().any(|ring| ring[0].0 < -60.));
+        for ring in rings {
+            for &(lat, lon) in ring.iter().filter(|p| p.0.abs() < 90.) {
+                let lon = (lon + 180.).rem_euclid(360.) - 180.;
+                let h = snapshot.calc(lat, lon, 0.).result.horizontal_intensity;
+                // linear interpolation on a one degree grid
+                assert_close(h, level, 100.);
+            }
+        }
+    }
+    // the middle of a zone that does not reach the pole
+    let ring = zones
+        .blackout
+        .iter()
+        .find(|ring| ring.iter().all(|p| p.0.abs() < 90.))
+        .unwrap();
+    let lat = ring.iter().map(|p| p.0).sum::<f64>() / ring.len() as f64;
+    let lon = ring.iter().map(|p| p.1).sum::<f64>() / ring.len() as f64;
+    let lon = (lon + 180.).rem_euclid(360.) - 180.;
+    assert_eq!(
+        snapshot.calc(lat, lon, 0.).compass_zone(),
+        CompassZone::Blackout
+    );
+}
+
+#[test]
+fn test_dip_poles() {
+    // the WMM2020 report gives the dip poles at 2020.0 as 86.50N 164.04E and 64.07S 135.88E
+    let snapshot = WMM::version(WmmVersion::Wmm2020).at(2020.);
+    let poles = snapshot.try_dip_poles(0.).unwrap();
+    assert_close(poles.north.0, 86.50, DEG_ABS_TOL);
+    assert_close(poles.north.1, 164.04, 0.02);
+    assert_close(poles.south.0, -64.07, DEG_ABS_TOL);
+    assert_close(poles.south.1, 135.88, 0.02);
+    for (lat, lon) in [poles.north, poles.south] {
+        let field = snapshot.calc(lat, lon, 0.);
+        assert_close(field.result.horizontal_intensity, 0., 1e-3);
+        assert_close(field.result.inclination.abs(), 90., 1e-6);
+    }
+    // the poles move up with the field lines
+    let high = snapshot.try_dip_poles(400.).unwrap();
+    assert!(high.north != poles.north);
+    assert_close(
+        snapshot
+            .calc(high.north.0, high.north.1, 400.)
+            .result
+            .horizontal_intensity,
+        0.,
+        1e-3,
+    );
+
+    // one row for every year of the validity
+    let track = PoleTrack::yearly(&WMM::version(WmmVersion::Wmm2020), 0.).unwrap();
+    let dates = track.rows.iter().map(|row| row.0).collect::<Vec<_>>();
+    assert_eq!(dates, [2020., 2021., 2022., 2023., 2024., 2025.]);
+    assert_eq!(track.rows[0].1, poles);
+}